@@ -0,0 +1,49 @@
+//! Reads arbitrary bytes as `Versioned<T>` in every built-in format. Errors are expected, but no input may panic the reader.
+//!
+//! Run with `cargo +nightly fuzz run versioned` from the `serde_migrate` directory.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use serde_migrate::{versioned, Bincode, CompactVersioned, Format, Json, Postcard, Versioned};
+
+#[versioned(id = 1, preserve_unknown)]
+struct Message {
+    #[version(end = 2)]
+    pub old: u32,
+    #[version(start = 2)]
+    pub new: u64,
+    pub items: Vec<Item>,
+    pub parent: Option<Item>,
+}
+
+impl message_migrations::Migrate for Message {
+    fn to_v2(v: message_migrations::MessageV1) -> message_migrations::MessageV2 {
+        message_migrations::MessageV2 { new: v.old as u64, items: v.items, parent: v.parent }
+    }
+}
+
+#[versioned(id = 2, min_supported = 2)]
+struct Item {
+    #[version(end = 3)]
+    pub name: String,
+    #[version(start = 3)]
+    pub names: Vec<String>,
+}
+
+impl item_migrations::Migrate for Item {
+    fn to_v3(v: item_migrations::ItemV2) -> item_migrations::ItemV3 {
+        item_migrations::ItemV3 { names: vec![v.name] }
+    }
+}
+
+fuzz_target!(|data: &[u8]| {
+    let _ = Json::from_bytes::<Versioned<Message>>(data);
+    let _ = Bincode::from_bytes::<Versioned<Message>>(data);
+    let _ = Postcard::from_bytes::<Versioned<Message>>(data);
+    let _ = bincode::deserialize::<CompactVersioned<Message>>(data);
+    let _ = postcard::from_bytes::<CompactVersioned<Message>>(data);
+    let _ = Json::peek_versions(data);
+    let _ = Bincode::peek_versions(data);
+    let _ = Postcard::peek_versions(data);
+});
@@ -0,0 +1,219 @@
+use std::collections::HashMap;
+
+use serde::{Serialize, Serializer, ser::{self, SerializeStruct}, Deserialize, Deserializer, de::{self, Visitor, SeqAccess, MapAccess}};
+
+use crate::{DeserializationState, HeaderLimits, VersionSerializer, Versioned, VersionedField, with_state};
+
+/// Like [`Versioned`], but with a compact version header for binary formats.
+///
+/// Every versioned type in the value must have a numeric id, given with `#[versioned(id = x)]`.
+/// When the format is not human-readable (e.g. bincode or postcard), the header is stored as `(u16 id, varint version)` pairs
+/// instead of a map from type names to versions. Human-readable formats like json use the same format as [`Versioned`].
+pub struct CompactVersioned<T>(pub T);
+
+/// Encodes the versions as `(u16 id, varint version)` pairs.
+fn encode_header(vs: VersionSerializer) -> Result<Vec<u8>, String> {
+    let mut header = Vec::with_capacity(vs.versions.len() * 3);
+    let mut used_ids = Vec::with_capacity(vs.versions.len());
+    for (name, version) in vs.versions {
+        let Some(&(_, id)) = vs.ids.iter().find(|(n, _)| *n == name) else {
+            return Err(format!("{} has no numeric id. Add #[versioned(id = x)] to use it with CompactVersioned", name));
+        };
+        if used_ids.contains(&id) {
+            return Err(format!("Numeric id {} is used by more than one type", id));
+        }
+        used_ids.push(id);
+
+        header.extend_from_slice(&id.to_le_bytes());
+        let mut v = version;
+        while v >= 0x80 {
+            header.push((v as u8) | 0x80);
+            v >>= 7;
+        }
+        header.push(v as u8);
+    }
+    Ok(header)
+}
+
+fn decode_header(mut header: &[u8], limits: &HeaderLimits) -> Result<HashMap<u16, u32>, String> {
+    let mut ids = HashMap::new();
+    while !header.is_empty() {
+        let [a, b, rest @ ..] = header else {
+            return Err("truncated type id".to_owned());
+        };
+        let id = u16::from_le_bytes([*a, *b]);
+        header = rest;
+
+        let mut version: u32 = 0;
+        let mut shift = 0;
+        loop {
+            let [byte, rest @ ..] = header else {
+                return Err("truncated version".to_owned());
+            };
+            header = rest;
+            if shift > 28 || (shift == 28 && byte & 0x70 != 0) {
+                return Err("version does not fit in a u32".to_owned());
+            }
+            version |= ((byte & 0x7f) as u32) << shift;
+            shift += 7;
+            if byte & 0x80 == 0 {
+                break;
+            }
+        }
+        if ids.len() >= limits.max_entries {
+            return Err(format!("more than {} entries", limits.max_entries));
+        }
+        if version > limits.max_version {
+            return Err(format!("version {} is higher than the limit of {}", version, limits.max_version));
+        }
+        if ids.insert(id, version).is_some() {
+            return Err(format!("duplicate type id {}", id));
+        }
+    }
+    Ok(ids)
+}
+
+impl<T: Serialize> Serialize for CompactVersioned<T> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        if serializer.is_human_readable() {
+            return Versioned(&self.0).serialize(serializer);
+        }
+
+        let mut vs = VersionSerializer::default();
+        self.0.serialize(&mut vs).unwrap();
+        let header = encode_header(vs).map_err(ser::Error::custom)?;
+        let mut s = serializer.serialize_struct("CompactVersioned", 2)?;
+        s.serialize_field("versions", &HeaderBytes(header))?;
+        s.serialize_field("value", &self.0)?;
+        s.end()
+    }
+}
+
+struct HeaderBytes(Vec<u8>);
+
+impl Serialize for HeaderBytes {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_bytes(&self.0)
+    }
+}
+
+struct HeaderBytesVisitor;
+
+impl<'de> Visitor<'de> for HeaderBytesVisitor {
+    type Value = HeaderBytes;
+
+    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        formatter.write_str("a compact version header")
+    }
+
+    fn visit_bytes<E>(self, v: &[u8]) -> Result<Self::Value, E> {
+        Ok(HeaderBytes(v.to_owned()))
+    }
+
+    fn visit_byte_buf<E>(self, v: Vec<u8>) -> Result<Self::Value, E> {
+        Ok(HeaderBytes(v))
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+    where
+        A: SeqAccess<'de>,
+    {
+        // An entry is at most 7 bytes: a 2 byte id, and a version of up to 5 bytes
+        let max_len = HeaderLimits::current().max_entries.saturating_mul(7);
+        let mut bytes = Vec::new();
+        while let Some(b) = seq.next_element()? {
+            if bytes.len() >= max_len {
+                return Err(de::Error::custom(format!("Invalid compact version header: more than {} entries", HeaderLimits::current().max_entries)));
+            }
+            bytes.push(b);
+        }
+        Ok(HeaderBytes(bytes))
+    }
+}
+
+impl<'de> Deserialize<'de> for HeaderBytes {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_bytes(HeaderBytesVisitor)
+    }
+}
+
+fn header_state<E: de::Error>(header: HeaderBytes) -> Result<DeserializationState, E> {
+    let ids = decode_header(&header.0, &HeaderLimits::current()).map_err(|e| de::Error::custom(format!("Invalid compact version header: {}", e)))?;
+    Ok(DeserializationState {
+        versions: Default::default(),
+        remaining_versions: Default::default(),
+        remaining_ids: ids,
+    })
+}
+
+struct CompactVersionedVisitor<T> {
+    _p: std::marker::PhantomData<T>,
+}
+
+impl<'de, T: Deserialize<'de>> Visitor<'de> for CompactVersionedVisitor<T> {
+    type Value = CompactVersioned<T>;
+
+    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        formatter.write_str("{ versions: bytes, value: T }")
+    }
+
+    fn visit_seq<V>(self, mut seq: V) -> Result<Self::Value, V::Error>
+    where
+        V: SeqAccess<'de>,
+    {
+        let header: HeaderBytes = seq.next_element()?
+            .ok_or_else(|| de::Error::invalid_length(0, &self))?;
+        let value = with_state(header_state(header)?, || seq.next_element())?
+            .ok_or_else(|| de::Error::invalid_length(1, &self))?;
+        Ok(CompactVersioned(value))
+    }
+
+    fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+    where
+        A: MapAccess<'de>,
+    {
+        let mut state = None;
+        while let Some(key) = map.next_key()? {
+            match key {
+                VersionedField::Versions => {
+                    if state.is_some() {
+                        return Err(de::Error::duplicate_field("versions"));
+                    }
+                    state = Some(header_state(map.next_value()?)?);
+                }
+                VersionedField::Version => {
+                    return Err(de::Error::unknown_field("version", &["versions", "value"]));
+                }
+                VersionedField::Value => {
+                    let Some(state) = state else {
+                        return Err(de::Error::custom("Missing field 'versions'. When deserializing a CompactVersioned<T>, the field 'versions' must be present before the field 'value'."));
+                    };
+                    let value = with_state(state, || map.next_value())?;
+                    return Ok(CompactVersioned(value));
+                }
+            }
+        }
+        Err(de::Error::missing_field("value"))
+    }
+}
+
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for CompactVersioned<T> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        if deserializer.is_human_readable() {
+            return Versioned::<T>::deserialize(deserializer).map(|v| CompactVersioned(v.0));
+        }
+        deserializer.deserialize_struct("CompactVersioned", &["versions", "value"], CompactVersionedVisitor { _p: std::marker::PhantomData })
+    }
+}
@@ -0,0 +1,158 @@
+use std::collections::HashMap;
+
+use serde::{Serialize, Serializer, ser::SerializeStruct, Deserialize, Deserializer, de::{self, Visitor, SeqAccess, MapAccess, DeserializeSeed, IntoDeserializer}};
+
+use crate::{HeaderSeed, UnknownValue, VersionSerializer, with_versions};
+
+/// A whole-document migration, from one schema version to the next.
+pub type SchemaMigration = fn(&mut SchemaDocument) -> Result<(), String>;
+
+/// A document at an older schema version, passed to each [`SchemaMigration`].
+pub struct SchemaDocument {
+    /// The per-type versions stored in the document. Migrations may change these if they change the data of versioned types.
+    pub versions: HashMap<String, u32>,
+    pub value: UnknownValue,
+}
+
+/// A type which is the root of a document with a single, document-level schema version.
+///
+/// See [`VersionedDocument`].
+pub trait Schema {
+    /// The chain of whole-document migrations. `MIGRATIONS[0]` migrates from schema version 1 to 2, and so on.
+    const MIGRATIONS: &'static [SchemaMigration];
+
+    /// The current schema version.
+    const SCHEMA_VERSION: u32 = Self::MIGRATIONS.len() as u32 + 1;
+}
+
+impl<T: Schema + ?Sized> Schema for &T {
+    const MIGRATIONS: &'static [SchemaMigration] = T::MIGRATIONS;
+}
+
+/// Like [`crate::Versioned`], but the envelope also carries a document-level schema version.
+///
+/// This is useful for applications that have a single schema version, with migrations that touch several types at once.
+/// When deserializing a document with an older schema version, the document is read as an [`UnknownValue`], and the
+/// migrations in [`Schema::MIGRATIONS`] run on it. After that, the value is deserialized as usual, and the per-type
+/// migrations run using the `versions` map.
+///
+/// Since older documents are read without knowing their types, schema migrations only work with self-describing formats like json.
+pub struct VersionedDocument<T>(pub T);
+
+impl<T: Serialize + Schema> Serialize for VersionedDocument<T> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut vs = VersionSerializer::default();
+        self.0.serialize(&mut vs).unwrap();
+        let mut s = serializer.serialize_struct("VersionedDocument", 3)?;
+        s.serialize_field("schema", &T::SCHEMA_VERSION)?;
+        s.serialize_field("versions", &vs.to_serialized_versions())?;
+        s.serialize_field("value", &self.0)?;
+        s.end()
+    }
+}
+
+/// Deserializes the value of a document, after running all schema migrations.
+struct DocumentValueSeed<T> {
+    schema: u32,
+    versions: HashMap<String, u32>,
+    _p: std::marker::PhantomData<T>,
+}
+
+impl<'de, T: Deserialize<'de> + Schema> DeserializeSeed<'de> for DocumentValueSeed<T> {
+    type Value = T;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        if self.schema == 0 || self.schema > T::SCHEMA_VERSION {
+            return Err(de::Error::custom(format!("Invalid schema version (got {}, the current schema version is {})", self.schema, T::SCHEMA_VERSION)));
+        }
+
+        if self.schema == T::SCHEMA_VERSION {
+            return with_versions(self.versions, || T::deserialize(deserializer));
+        }
+
+        let mut document = SchemaDocument {
+            versions: self.versions,
+            value: UnknownValue::deserialize(deserializer)?,
+        };
+        for (i, migration) in T::MIGRATIONS.iter().enumerate().skip(self.schema as usize - 1) {
+            migration(&mut document).map_err(|e| de::Error::custom(format!("Schema migration from version {} to {} failed: {}", i + 1, i + 2, e)))?;
+        }
+        with_versions(document.versions, || T::deserialize(document.value.into_deserializer()))
+    }
+}
+
+struct VersionedDocumentVisitor<T> {
+    _p: std::marker::PhantomData<T>,
+}
+
+#[derive(serde::Deserialize)]
+#[serde(field_identifier, rename_all = "lowercase")]
+enum DocumentField { Schema, Versions, Value }
+
+impl<'de, T: Deserialize<'de> + Schema> Visitor<'de> for VersionedDocumentVisitor<T> {
+    type Value = VersionedDocument<T>;
+
+    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        formatter.write_str("{ schema: u32, versions: HashMap<String, u32>, value: T }")
+    }
+
+    fn visit_seq<V>(self, mut seq: V) -> Result<Self::Value, V::Error>
+    where
+        V: SeqAccess<'de>,
+    {
+        let schema: u32 = seq.next_element()?
+            .ok_or_else(|| de::Error::invalid_length(0, &self))?;
+        let versions = seq.next_element_seed(HeaderSeed)?
+            .ok_or_else(|| de::Error::invalid_length(1, &self))?;
+        let value = seq.next_element_seed(DocumentValueSeed::<T> { schema, versions, _p: std::marker::PhantomData })?
+            .ok_or_else(|| de::Error::invalid_length(2, &self))?;
+        Ok(VersionedDocument(value))
+    }
+
+    fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+    where
+        A: MapAccess<'de>,
+    {
+        let mut schema = None;
+        let mut versions = None;
+        while let Some(key) = map.next_key()? {
+            match key {
+                DocumentField::Schema => {
+                    if schema.is_some() {
+                        return Err(de::Error::duplicate_field("schema"));
+                    }
+                    schema = Some(map.next_value()?);
+                }
+                DocumentField::Versions => {
+                    if versions.is_some() {
+                        return Err(de::Error::duplicate_field("versions"));
+                    }
+                    versions = Some(map.next_value_seed(HeaderSeed)?);
+                }
+                DocumentField::Value => {
+                    let (Some(schema), Some(versions)) = (schema, versions) else {
+                        return Err(de::Error::custom("Missing field 'schema' or 'versions'. When deserializing a VersionedDocument<T>, the fields 'schema' and 'versions' must be present before the field 'value'."));
+                    };
+                    let value = map.next_value_seed(DocumentValueSeed::<T> { schema, versions, _p: std::marker::PhantomData })?;
+                    return Ok(VersionedDocument(value));
+                }
+            }
+        }
+        Err(de::Error::missing_field("value"))
+    }
+}
+
+impl<'de, T: Deserialize<'de> + Schema> Deserialize<'de> for VersionedDocument<T> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_struct("VersionedDocument", &["schema", "versions", "value"], VersionedDocumentVisitor { _p: std::marker::PhantomData })
+    }
+}
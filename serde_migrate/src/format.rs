@@ -0,0 +1,181 @@
+use std::{fmt::Display, io::{self, BufRead, Read, Write}};
+
+use serde::{Serialize, de::DeserializeOwned};
+
+use crate::VersionMap;
+
+/// A serialization format, used by helpers like [`crate::upgrade`] that work on raw bytes.
+///
+/// Implementations are provided for json, bincode and postcard, behind the features of the same names.
+pub trait Format {
+    /// A short name of the format, like `"json"`.
+    const NAME: &'static str;
+
+    fn to_bytes<T: Serialize + ?Sized>(value: &T) -> Result<Vec<u8>, FormatError>;
+
+    fn from_bytes<T: DeserializeOwned>(input: &[u8]) -> Result<T, FormatError>;
+
+    /// Reads the version header of data serialized with [`crate::Versioned`]. See [`crate::peek_versions`].
+    fn peek_versions(input: &[u8]) -> Result<VersionMap, FormatError>;
+
+    /// Reads the next record from a stream of records into `buf`. Returns `false` at the end of the stream.
+    ///
+    /// By default, each record is prefixed with its length as a varint.
+    fn read_record<R: BufRead>(reader: &mut R, buf: &mut Vec<u8>) -> io::Result<bool> {
+        let Some(len) = read_varint(reader)? else {
+            return Ok(false);
+        };
+        buf.clear();
+        // Read incrementally, so that a corrupt length does not allocate a huge buffer up front
+        reader.by_ref().take(len).read_to_end(buf)?;
+        if (buf.len() as u64) < len {
+            return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "truncated record"));
+        }
+        Ok(true)
+    }
+
+    /// Writes a record to a stream of records, so that it can be read by [`Format::read_record`].
+    fn write_record<W: Write>(writer: &mut W, record: &[u8]) -> io::Result<()> {
+        let mut len = record.len() as u64;
+        while len >= 0x80 {
+            writer.write_all(&[(len as u8) | 0x80])?;
+            len >>= 7;
+        }
+        writer.write_all(&[len as u8])?;
+        writer.write_all(record)
+    }
+}
+
+/// Reads a LEB128 varint. Returns `None` if the stream ends before the first byte.
+fn read_varint<R: BufRead>(reader: &mut R) -> io::Result<Option<u64>> {
+    let mut value: u64 = 0;
+    let mut shift = 0;
+    loop {
+        let mut byte = [0];
+        if reader.read(&mut byte)? == 0 {
+            if shift == 0 {
+                return Ok(None);
+            }
+            return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "truncated record length"));
+        }
+        if shift > 63 {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "record length does not fit in a u64"));
+        }
+        value |= ((byte[0] & 0x7f) as u64) << shift;
+        shift += 7;
+        if byte[0] & 0x80 == 0 {
+            return Ok(Some(value));
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct FormatError {
+    message: String
+}
+
+impl FormatError {
+    pub fn new(message: impl Display) -> Self {
+        FormatError {
+            message: message.to_string()
+        }
+    }
+}
+
+impl Display for FormatError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for FormatError {}
+
+impl From<std::io::Error> for FormatError {
+    fn from(e: std::io::Error) -> Self {
+        FormatError::new(e)
+    }
+}
+
+#[cfg(feature = "json")]
+pub struct Json;
+
+#[cfg(feature = "json")]
+impl Format for Json {
+    const NAME: &'static str = "json";
+
+    fn to_bytes<T: Serialize + ?Sized>(value: &T) -> Result<Vec<u8>, FormatError> {
+        serde_json::to_vec(value).map_err(FormatError::new)
+    }
+
+    fn from_bytes<T: DeserializeOwned>(input: &[u8]) -> Result<T, FormatError> {
+        serde_json::from_slice(input).map_err(FormatError::new)
+    }
+
+    fn peek_versions(input: &[u8]) -> Result<VersionMap, FormatError> {
+        crate::peek_versions(&mut serde_json::Deserializer::from_slice(input)).map_err(FormatError::new)
+    }
+
+    /// Records are newline-delimited. Empty lines are skipped.
+    fn read_record<R: BufRead>(reader: &mut R, buf: &mut Vec<u8>) -> io::Result<bool> {
+        loop {
+            buf.clear();
+            if reader.read_until(b'\n', buf)? == 0 {
+                return Ok(false);
+            }
+            while matches!(buf.last(), Some(b'\n' | b'\r')) {
+                buf.pop();
+            }
+            if !buf.iter().all(u8::is_ascii_whitespace) {
+                return Ok(true);
+            }
+        }
+    }
+
+    fn write_record<W: Write>(writer: &mut W, record: &[u8]) -> io::Result<()> {
+        writer.write_all(record)?;
+        writer.write_all(b"\n")
+    }
+}
+
+/// Bincode, with the same options as `bincode::serialize` and `bincode::deserialize`.
+#[cfg(feature = "bincode")]
+pub struct Bincode;
+
+#[cfg(feature = "bincode")]
+impl Format for Bincode {
+    const NAME: &'static str = "bincode";
+
+    fn to_bytes<T: Serialize + ?Sized>(value: &T) -> Result<Vec<u8>, FormatError> {
+        bincode::serialize(value).map_err(FormatError::new)
+    }
+
+    fn from_bytes<T: DeserializeOwned>(input: &[u8]) -> Result<T, FormatError> {
+        bincode::deserialize(input).map_err(FormatError::new)
+    }
+
+    fn peek_versions(input: &[u8]) -> Result<VersionMap, FormatError> {
+        use bincode::Options;
+        let options = bincode::DefaultOptions::new().with_fixint_encoding().allow_trailing_bytes();
+        crate::peek_versions(&mut bincode::Deserializer::from_slice(input, options)).map_err(FormatError::new)
+    }
+}
+
+#[cfg(feature = "postcard")]
+pub struct Postcard;
+
+#[cfg(feature = "postcard")]
+impl Format for Postcard {
+    const NAME: &'static str = "postcard";
+
+    fn to_bytes<T: Serialize + ?Sized>(value: &T) -> Result<Vec<u8>, FormatError> {
+        postcard::to_stdvec(value).map_err(FormatError::new)
+    }
+
+    fn from_bytes<T: DeserializeOwned>(input: &[u8]) -> Result<T, FormatError> {
+        postcard::from_bytes(input).map_err(FormatError::new)
+    }
+
+    fn peek_versions(input: &[u8]) -> Result<VersionMap, FormatError> {
+        crate::peek_versions(&mut postcard::Deserializer::from_bytes(input)).map_err(FormatError::new)
+    }
+}
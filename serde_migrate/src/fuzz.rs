@@ -0,0 +1,99 @@
+use arbitrary::Unstructured;
+use serde::{Serialize, de::DeserializeOwned};
+
+use crate::{Format, VersionMap, Versioned, VersionedType, versions_of};
+
+/// Implemented by the #[macro@crate::versioned] macro for types with `#[versioned(arbitrary)]`.
+///
+/// Generates random data stored with any version of a type, for property-based testing and fuzzing of the migrations.
+pub trait ArbitraryVersions: VersionedType {
+    /// Generates a random value of the given version, and serializes it with a header for that version.
+    ///
+    /// Returns [`arbitrary::Error::IncorrectFormat`] if the version is not supported, or if the format cannot round-trip the generated value, e.g. a NaN in json.
+    fn arbitrary_version_bytes<F: Format>(version: u32, u: &mut Unstructured<'_>) -> arbitrary::Result<Vec<u8>>;
+}
+
+/// Used by the macro to serialize a generated value of an old version, with a header for that version.
+#[doc(hidden)]
+pub fn arbitrary_version_bytes<T, V, F>(version: u32, value: &V) -> arbitrary::Result<Vec<u8>>
+where
+    T: VersionedType,
+    V: Serialize + DeserializeOwned,
+    F: Format,
+{
+    // Values that the format cannot represent are not the fault of the migrations
+    let roundtrips = F::to_bytes(value).is_ok_and(|bytes| F::from_bytes::<V>(&bytes).is_ok());
+    if !roundtrips {
+        return Err(arbitrary::Error::IncorrectFormat);
+    }
+
+    #[derive(Serialize)]
+    #[serde(rename = "Versioned")]
+    struct Envelope<'a, V> {
+        versions: VersionMap,
+        value: &'a V,
+    }
+    let mut versions = versions_of(value);
+    versions.insert(T::type_key().to_owned(), version);
+    F::to_bytes(&Envelope { versions, value }).map_err(|_| arbitrary::Error::IncorrectFormat)
+}
+
+/// Generates data stored with a random version of `T`, and checks that it deserializes and migrates to a valid value.
+///
+/// The migrated value must also survive a round-trip at the latest version. Returns the migrated value.
+/// This is meant to be called from a fuzz target, or from a property-based test with [`assert_random_migrations`].
+///
+/// # Panics
+///
+/// Panics if the data cannot be deserialized, or if a migration panics.
+pub fn assert_arbitrary_migrates<T, F>(u: &mut Unstructured<'_>) -> arbitrary::Result<T>
+where
+    T: ArbitraryVersions + Serialize + DeserializeOwned,
+    F: Format,
+{
+    let history = T::history();
+    let version = history[u.choose_index(history.len())?].version;
+    let bytes = T::arbitrary_version_bytes::<F>(version, u)?;
+
+    let value = match F::from_bytes::<Versioned<T>>(&bytes) {
+        Ok(value) => value.0,
+        Err(e) => panic!("Random data of {} version {} could not be deserialized from {}: {}", T::type_key(), version, F::NAME, e),
+    };
+    let roundtrip = F::to_bytes(&Versioned(&value)).and_then(|bytes| F::from_bytes::<Versioned<T>>(&bytes));
+    match roundtrip {
+        Ok(value) => Ok(value.0),
+        Err(e) => panic!("Random data of {} version {} could not be round-tripped in {} after migrating: {}", T::type_key(), version, F::NAME, e),
+    }
+}
+
+/// Runs [`assert_arbitrary_migrates`] on `iterations` buffers of pseudo-random bytes.
+///
+/// The bytes are generated from a fixed seed, so failures are reproducible. Returns the number of values that were generated and migrated,
+/// which can be lower than `iterations` if some random values could not be represented in the format.
+///
+/// # Panics
+///
+/// Panics if any value fails to deserialize, or if a migration panics.
+pub fn assert_random_migrations<T, F>(iterations: usize) -> usize
+where
+    T: ArbitraryVersions + Serialize + DeserializeOwned,
+    F: Format,
+{
+    // xorshift64, which is good enough to drive `Unstructured`
+    let mut state = 0x2545_f491_4f6c_dd1d_u64;
+    let mut buf = vec![0u8; 1024];
+    let mut migrated = 0;
+    for _ in 0..iterations {
+        for chunk in buf.chunks_mut(8) {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            chunk.copy_from_slice(&state.to_le_bytes()[..chunk.len()]);
+        }
+        let len = (state % buf.len() as u64) as usize;
+        if assert_arbitrary_migrates::<T, F>(&mut Unstructured::new(&buf[..len])).is_ok() {
+            migrated += 1;
+        }
+    }
+    migrated
+}
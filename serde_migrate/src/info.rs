@@ -0,0 +1,155 @@
+use std::fmt::Display;
+
+/// Implemented by the #[macro@crate::versioned] macro.
+///
+/// Describes the history of a versioned type at runtime, e.g. for admin pages or migration dashboards.
+pub trait VersionedType {
+    /// The latest version, which is used when serializing.
+    const CURRENT_VERSION: u32;
+    /// The oldest version which can still be deserialized. See `#[versioned(min_supported = x)]`.
+    const MIN_SUPPORTED_VERSION: u32;
+
+    /// The name of the type in version headers.
+    fn type_key() -> &'static str;
+
+    /// Every supported version, from the oldest to the current one.
+    fn history() -> &'static [VersionInfo];
+
+    /// What changed in every version, computed from [`VersionedType::history`].
+    fn changelog() -> Changelog {
+        Changelog::from_history(Self::type_key(), Self::history())
+    }
+}
+
+/// A version of a versioned type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VersionInfo {
+    pub version: u32,
+    /// The fields present in this version, in declaration order.
+    pub fields: &'static [FieldInfo],
+}
+
+/// A field of a versioned type, as declared with `#[version(start = x, end = y)]`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FieldInfo {
+    /// The name of the field in rust. Serde attributes like `rename` are not applied.
+    pub name: &'static str,
+    /// The type of the field, as written in the source.
+    pub ty: &'static str,
+    /// The first version with the field. Fields added before the minimum supported version start at that version.
+    pub start: u32,
+    /// The version in which the field was removed, if any.
+    pub end: Option<u32>,
+}
+
+impl VersionInfo {
+    pub fn field(&self, name: &str) -> Option<&'static FieldInfo> {
+        self.fields.iter().find(|f| f.name == name)
+    }
+}
+
+/// A change of a field between two versions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FieldChange {
+    Added(&'static FieldInfo),
+    Removed(&'static FieldInfo),
+    /// The field was removed and added again with the same name, usually with a different type.
+    Changed { from: &'static FieldInfo, to: &'static FieldInfo },
+}
+
+impl Display for FieldChange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FieldChange::Added(field) => write!(f, "added `{}: {}`", field.name, field.ty),
+            FieldChange::Removed(field) => write!(f, "removed `{}: {}`", field.name, field.ty),
+            FieldChange::Changed { from, to } => write!(f, "changed `{}` from `{}` to `{}`", to.name, from.ty, to.ty),
+        }
+    }
+}
+
+/// The changes in a single version.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChangelogEntry {
+    pub version: u32,
+    /// For the oldest supported version, all of its fields are listed as added.
+    pub changes: Vec<FieldChange>,
+}
+
+impl Display for ChangelogEntry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "v{}: ", self.version)?;
+        if self.changes.is_empty() {
+            return write!(f, "no changes");
+        }
+        for (i, change) in self.changes.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{}", change)?;
+        }
+        Ok(())
+    }
+}
+
+/// What changed in every version of a versioned type. See [`VersionedType::changelog`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Changelog {
+    pub type_key: &'static str,
+    /// One entry per supported version, from the oldest to the current one.
+    pub entries: Vec<ChangelogEntry>,
+}
+
+impl Changelog {
+    pub fn from_history(type_key: &'static str, history: &'static [VersionInfo]) -> Self {
+        let mut entries = Vec::with_capacity(history.len());
+        let mut previous: &[FieldInfo] = &[];
+        for info in history {
+            let mut changes = vec![];
+            for field in previous {
+                if info.fields.contains(field) {
+                    continue;
+                }
+                match info.fields.iter().find(|f| f.name == field.name && !previous.contains(f)) {
+                    Some(to) => changes.push(FieldChange::Changed { from: field, to }),
+                    None => changes.push(FieldChange::Removed(field)),
+                }
+            }
+            for field in info.fields {
+                let changed = previous.iter().any(|f| f.name == field.name && !info.fields.contains(f));
+                if !previous.contains(field) && !changed {
+                    changes.push(FieldChange::Added(field));
+                }
+            }
+            entries.push(ChangelogEntry { version: info.version, changes });
+            previous = info.fields;
+        }
+        Changelog { type_key, entries }
+    }
+
+    /// Renders the changelog as Markdown, with the newest version first.
+    pub fn to_markdown(&self) -> String {
+        let mut out = format!("## {}\n", self.type_key);
+        for entry in self.entries.iter().rev() {
+            out += &format!("\n### Version {}\n\n", entry.version);
+            if entry.changes.is_empty() {
+                out += "* No changes\n";
+            }
+            for change in &entry.changes {
+                let change = change.to_string();
+                let mut chars = change.chars();
+                let first = chars.next().map(|c| c.to_uppercase().to_string()).unwrap_or_default();
+                out += &format!("* {}{}\n", first, chars.as_str());
+            }
+        }
+        out
+    }
+}
+
+impl Display for Changelog {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for entry in &self.entries {
+            writeln!(f, "{}", entry)?;
+        }
+        Ok(())
+    }
+}
@@ -74,6 +74,15 @@
 //! }
 //! ```
 //!
+//! ## Features
+//!
+//! * `json`, `bincode`, `postcard` (enabled by default) - Implementations of [`Format`] for the format of the same name, used by helpers like [`upgrade`] that work on raw bytes.
+//! * `testing` - The [`testing`](crate::testing) module, with helpers for testing migrations against stored fixtures. See [Testing migrations with fixtures](#testing-migrations-with-fixtures).
+//! * `schemars` - Json schemas for every version of types with `#[versioned(json_schema)]`, and for the `Versioned` envelope. See [Json schemas](#json-schemas).
+//! * `arbitrary` - Random data for every version of types with `#[versioned(arbitrary)]`, for property-based testing and fuzzing of migrations. See [Fuzzing migrations](#fuzzing-migrations).
+//! * `redb` - [`VersionedTable`], a [`redb`] table which writes migrated values back when they are read. See [Embedded key-value stores](#embedded-key-value-stores).
+//! * `rusqlite` - `ToSql` and `FromSql` for [`Versioned`], and [`upgrade_sqlite_column`] to upgrade a whole column. See [SQLite columns](#sqlite-columns).
+//!
 //! ## Attributes
 //!
 //! The following attributes are available:
@@ -84,6 +93,14 @@
 //!
 //! The current version will be assumed to be the greatest version mentioned by any attribute. The first version is always version 1.
 //!
+//! The root attribute also takes some options:
+//!
+//! * `#[versioned(min_supported = x)]` - Versions older than x can no longer be deserialized. See [Dropping old versions](#dropping-old-versions).
+//! * `#[versioned(id = x)]` - Gives the type a numeric id, which is used instead of the type name in compact version headers. See [Compact version headers](#compact-version-headers).
+//! * `#[versioned(preserve_unknown)]` - Fields that are not known by the latest version are kept in a hidden `unknown_fields` field, and are written back when serializing. See [Preserving unknown fields](#preserving-unknown-fields).
+//! * `#[versioned(json_schema)]` - Derives `schemars::JsonSchema` for every version. Requires the `schemars` feature. See [Json schemas](#json-schemas).
+//! * `#[versioned(arbitrary)]` - Derives `arbitrary::Arbitrary` and `Serialize` for every version. Requires the `arbitrary` feature. See [Fuzzing migrations](#fuzzing-migrations).
+//!
 //! ## Compatibility
 //!
 //! The #[macro@versioned] macro may currently be used on the following data types:
@@ -132,6 +149,429 @@
 //! }
 //! ```
 //!
+//! ### Moving fields between nested types
+//!
+//! Nested versioned types are migrated to their latest version before the migrations of the outer type run.
+//! If a field is moved from an inner type to the outer type, the outer migration needs to see the inner value at the version it was stored with.
+//! You can get this by declaring the field as [`Stored<T>`] in the older versions of the outer type, using the same trick as when changing types.
+//! The data format is unchanged.
+//!
+//! ```rust
+//! # use serde_migrate::{versioned, Versioned, Stored};
+//!
+//! #[versioned]
+//! #[derive(PartialEq, Debug)]
+//! struct Inner {
+//!     pub a: u32,
+//!     #[version(end = 2)]
+//!     pub moved: u32,
+//! }
+//!
+//! impl inner_migrations::Migrate for Inner {
+//!     fn to_v2(v: inner_migrations::InnerV1) -> inner_migrations::InnerV2 {
+//!         inner_migrations::InnerV2 { a: v.a }
+//!     }
+//! }
+//!
+//! #[versioned]
+//! #[derive(PartialEq, Debug)]
+//! struct Outer {
+//!     #[version(end = 2)]
+//!     pub inner: Stored<Inner>,
+//!     #[version(start = 2)]
+//!     pub inner: Inner,
+//!     #[version(start = 2)]
+//!     pub moved: u32,
+//! }
+//!
+//! impl outer_migrations::Migrate for Outer {
+//!     fn to_v2(v: outer_migrations::OuterV1) -> outer_migrations::OuterV2 {
+//!         let moved = match v.inner.get() {
+//!             inner_migrations::DataVersions::V1(inner) => inner.moved,
+//!             inner_migrations::DataVersions::V2(_) => 0,
+//!         };
+//!         outer_migrations::OuterV2 {
+//!             inner: v.inner.migrate(),
+//!             moved,
+//!         }
+//!     }
+//! }
+//!
+//! fn main() {
+//!     let decoded: Outer = serde_json::from_str::<Versioned<_>>(r#"{ "versions": { "rust_out::Outer": 1, "rust_out::Inner": 1 }, "value": { "inner": { "a": 1, "moved": 2 } } }"#).unwrap().0;
+//!     assert_eq!(decoded, Outer {
+//!         inner: Inner { a: 1 },
+//!         moved: 2,
+//!     });
+//! }
+//! ```
+//!
+//! ### Document-level schema versions
+//!
+//! If your application has a single schema version, with migrations that touch several types at once, you can use [`VersionedDocument`] instead of [`Versioned`].
+//! The root type implements [`Schema`], which lists a chain of whole-document migrations. These run on a generic [`UnknownValue`] representation of the document,
+//! before it is deserialized. The per-type `versions` map is still stored, and the per-type migrations run afterwards.
+//!
+//! ```rust
+//! # use serde_migrate::{versioned, Schema, SchemaDocument, SchemaMigration, UnknownValue, VersionedDocument};
+//!
+//! #[versioned]
+//! #[derive(PartialEq, Debug)]
+//! struct Settings {
+//!     pub theme: String,
+//! }
+//!
+//! impl Schema for Settings {
+//!     const MIGRATIONS: &'static [SchemaMigration] = &[rename_colors];
+//! }
+//!
+//! // Schema version 1 called the field `colors`.
+//! fn rename_colors(doc: &mut SchemaDocument) -> Result<(), String> {
+//!     let colors = doc.value.remove("colors").ok_or("missing field colors")?;
+//!     doc.value.insert("theme", colors);
+//!     Ok(())
+//! }
+//!
+//! fn main() {
+//!     let decoded: Settings = serde_json::from_str::<VersionedDocument<_>>(r#"{ "schema": 1, "versions": {}, "value": { "colors": "dark" } }"#).unwrap().0;
+//!     assert_eq!(decoded, Settings { theme: "dark".to_string() });
+//!
+//!     let encoded = serde_json::to_string(&VersionedDocument(&decoded)).unwrap();
+//!     assert_eq!(encoded, r#"{"schema":2,"versions":{"rust_out::Settings":1},"value":{"theme":"dark"}}"#);
+//! }
+//! ```
+//!
+//! ### Compact version headers
+//!
+//! The version header stores the full type name of every versioned type, which can be bigger than the value itself for small messages in binary formats.
+//! If you give every versioned type a numeric id with `#[versioned(id = x)]`, you can use [`CompactVersioned`] instead of [`Versioned`].
+//! In formats that are not human-readable, like bincode and postcard, the header is then stored as `(u16 id, varint version)` pairs.
+//! Human-readable formats like json still use type names.
+//!
+//! The ids must be unique among the types stored in the same value, and must never change.
+//!
+//! ```rust
+//! # use serde_migrate::{versioned, CompactVersioned};
+//!
+//! #[versioned(id = 1)]
+//! #[derive(PartialEq, Debug)]
+//! struct MyStruct {
+//!    pub value: u32,
+//! }
+//!
+//! fn main() {
+//!   let encoded = postcard::to_stdvec(&CompactVersioned(&MyStruct { value: 123 })).unwrap();
+//!   // 1 byte for the header length, 2 bytes for the id, 1 byte for the version and 1 byte for the value.
+//!   assert_eq!(encoded.len(), 5);
+//!   let decoded: MyStruct = postcard::from_bytes::<CompactVersioned<_>>(&encoded).unwrap().0;
+//!   assert_eq!(decoded, MyStruct { value: 123 });
+//! }
+//! ```
+//!
+//! ### Inspecting the version header
+//!
+//! Use [`peek_versions`] to find out which types and versions some data contains, without deserializing the value.
+//!
+//! ```rust
+//! # use serde_migrate::{versioned, Versioned};
+//!
+//! #[versioned]
+//! struct MyStruct {
+//!    pub value: u32,
+//! }
+//!
+//! fn main() {
+//!   let encoded = serde_json::to_string(&Versioned(&MyStruct { value: 123 })).unwrap();
+//!   let versions = serde_migrate::peek_versions(&mut serde_json::Deserializer::from_str(&encoded)).unwrap();
+//!   assert_eq!(versions["rust_out::MyStruct"], 1);
+//! }
+//! ```
+//!
+//! ### Upgrading stored data
+//!
+//! [`upgrade`] reads data in a given [`Format`], runs all migrations, and writes it back at the latest version.
+//! The returned [`UpgradeReport`] tells whether the bytes changed, and which types were migrated.
+//! Use [`upgrade_dry_run`] to only get the report.
+//!
+//! ```rust
+//! # use serde_migrate::{versioned, Json};
+//!
+//! #[versioned]
+//! #[derive(PartialEq, Debug)]
+//! struct MyStruct {
+//!     #[version(end = 2)]
+//!     pub a: u32,
+//!     #[version(start = 2)]
+//!     pub b: u32,
+//! }
+//!
+//! impl mystruct_migrations::Migrate for MyStruct {
+//!     fn to_v2(v: mystruct_migrations::MyStructV1) -> mystruct_migrations::MyStructV2 {
+//!         mystruct_migrations::MyStructV2 { b: v.a }
+//!     }
+//! }
+//!
+//! fn main() {
+//!     let input = br#"{"versions":{"rust_out::MyStruct":1},"value":{"a":123}}"#;
+//!     let mut output = Vec::new();
+//!     let report = serde_migrate::upgrade::<MyStruct, Json>(input, &mut output).unwrap();
+//!     assert!(report.changed);
+//!     assert_eq!(report.migrations().collect::<Vec<_>>(), vec![("rust_out::MyStruct", 1, 2)]);
+//!     assert_eq!(output, br#"{"versions":{"rust_out::MyStruct":2},"value":{"b":123}}"#);
+//! }
+//! ```
+//!
+//! ### Inspecting the history of a type
+//!
+//! Every versioned type implements [`VersionedType`], which describes the fields of every supported version at runtime.
+//!
+//! ```rust
+//! # use serde_migrate::{versioned, VersionedType};
+//!
+//! #[versioned]
+//! struct MyStruct {
+//!     pub a: u32,
+//!     #[version(start = 2)]
+//!     pub b: Vec<String>,
+//! }
+//!
+//! impl mystruct_migrations::Migrate for MyStruct {
+//!     fn to_v2(v: mystruct_migrations::MyStructV1) -> mystruct_migrations::MyStructV2 {
+//!         mystruct_migrations::MyStructV2 { a: v.a, b: vec![] }
+//!     }
+//! }
+//!
+//! fn main() {
+//!     assert_eq!(MyStruct::CURRENT_VERSION, 2);
+//!     assert_eq!(MyStruct::type_key(), "rust_out::MyStruct");
+//!     let v2 = &MyStruct::history()[1];
+//!     assert_eq!(v2.fields.iter().map(|f| (f.name, f.ty)).collect::<Vec<_>>(), vec![("a", "u32"), ("b", "Vec<String>")]);
+//!
+//!     assert_eq!(MyStruct::changelog().to_string(), "v1: added `a: u32`\nv2: added `b: Vec<String>`\n");
+//! }
+//! ```
+//!
+//! [`VersionedType::changelog`] lists what changed in every version, and [`Changelog::to_markdown`] renders it for publishing.
+//!
+//! ### Migrating types that are only known at runtime
+//!
+//! Tools that handle many types, like admin interfaces, can register them in a [`MigrationRegistry`], and migrate data by its type key.
+//! The type key is the name used in the version header.
+//!
+//! ```rust
+//! # use serde_migrate::{versioned, Json, MigrationRegistry};
+//!
+//! #[versioned]
+//! struct MyStruct {
+//!     #[version(end = 2)]
+//!     pub a: u32,
+//!     #[version(start = 2)]
+//!     pub b: u32,
+//! }
+//!
+//! impl mystruct_migrations::Migrate for MyStruct {
+//!     fn to_v2(v: mystruct_migrations::MyStructV1) -> mystruct_migrations::MyStructV2 {
+//!         mystruct_migrations::MyStructV2 { b: v.a }
+//!     }
+//! }
+//!
+//! fn main() {
+//!     let mut registry = MigrationRegistry::new();
+//!     registry.register::<MyStruct>();
+//!
+//!     let input = br#"{"versions":{"rust_out::MyStruct":1},"value":{"a":123}}"#;
+//!     let mut output = Vec::new();
+//!     registry.upgrade::<Json>("rust_out::MyStruct", input, &mut output).unwrap();
+//!     assert_eq!(output, br#"{"versions":{"rust_out::MyStruct":2},"value":{"b":123}}"#);
+//!
+//!     let value = serde_json::from_slice(input).unwrap();
+//!     let migrated = registry.migrate_json("rust_out::MyStruct", value).unwrap();
+//!     assert_eq!(migrated["value"]["b"], 123);
+//! }
+//! ```
+//!
+//! ### Migrating json values in memory
+//!
+//! With the `json` feature, [`MigrateValue::migrate_value`] migrates a `serde_json::Value` without serializing it to text.
+//! The versions are given separately, e.g. from [`peek_versions`] or a [`VersionMap`] stored next to the value.
+//!
+//! ```rust
+//! # use serde_migrate::{versioned, MigrateValue, VersionMap};
+//!
+//! #[versioned]
+//! struct MyStruct {
+//!     #[version(end = 2)]
+//!     pub a: u32,
+//!     #[version(start = 2)]
+//!     pub b: u32,
+//! }
+//!
+//! impl mystruct_migrations::Migrate for MyStruct {
+//!     fn to_v2(v: mystruct_migrations::MyStructV1) -> mystruct_migrations::MyStructV2 {
+//!         mystruct_migrations::MyStructV2 { b: v.a }
+//!     }
+//! }
+//!
+//! fn main() {
+//!     let versions = VersionMap::from([("rust_out::MyStruct".to_string(), 1)]);
+//!     let value = MyStruct::migrate_value(serde_json::json!({ "a": 123 }), &versions).unwrap();
+//!     assert_eq!(value, serde_json::json!({ "b": 123 }));
+//! }
+//! ```
+//!
+//! ### Json schemas
+//!
+//! With the `schemars` feature, `#[versioned(json_schema)]` derives `schemars::JsonSchema` for every version of a type.
+//! The type itself has the schema of the latest version, and `json_schema_for_version(n)` returns the schema of version n, for validating data which has not been migrated yet.
+//! `Versioned<T>` has a schema for the whole envelope, with the `versions` header and the latest version of the value.
+//!
+//! ```rust
+//! # #[cfg(feature = "schemars")]
+//! # mod example {
+//! # use serde_migrate::{versioned, Versioned};
+//! #[versioned(json_schema)]
+//! struct MyStruct {
+//!     #[version(end = 2)]
+//!     pub a: u32,
+//!     #[version(start = 2)]
+//!     pub b: String,
+//! }
+//!
+//! impl mystruct_migrations::Migrate for MyStruct {
+//!     fn to_v2(v: mystruct_migrations::MyStructV1) -> mystruct_migrations::MyStructV2 {
+//!         mystruct_migrations::MyStructV2 { b: v.a.to_string() }
+//!     }
+//! }
+//!
+//! pub fn main() {
+//!     let v1 = MyStruct::json_schema_for_version(1).unwrap();
+//!     assert_eq!(v1.get("required").unwrap(), &serde_json::json!(["a"]));
+//!
+//!     let envelope = serde_migrate::schemars::schema_for!(Versioned<MyStruct>);
+//!     assert_eq!(envelope.get("required").unwrap(), &serde_json::json!(["versions", "value"]));
+//! }
+//! # }
+//! # fn main() {
+//! #     #[cfg(feature = "schemars")]
+//! #     example::main();
+//! # }
+//! ```
+//!
+//! ### Streams of records
+//!
+//! For large logs of records, like newline-delimited json or length-prefixed postcard records, [`RecordReader`] reads and migrates one record at a time.
+//! [`upgrade_records`] also writes the upgraded records to an output stream. Records that cannot be read are reported, without aborting the stream.
+//!
+//! ```rust
+//! # use serde_migrate::{versioned, Json, RecordReader};
+//!
+//! #[versioned]
+//! #[derive(PartialEq, Debug)]
+//! struct Event {
+//!    pub id: u32,
+//! }
+//!
+//! fn main() {
+//!     let log = br#"{"versions":{"rust_out::Event":1},"value":{"id":1}}
+//! not json
+//! {"versions":{"rust_out::Event":1},"value":{"id":2}}
+//! "#;
+//!     let events: Vec<_> = RecordReader::<_, Event, Json>::new(&log[..]).collect();
+//!     assert_eq!(events.len(), 3);
+//!     assert_eq!(events[0].as_ref().unwrap(), &Event { id: 1 });
+//!     assert_eq!(events[1].as_ref().unwrap_err().index, 1);
+//!     assert_eq!(events[2].as_ref().unwrap(), &Event { id: 2 });
+//! }
+//! ```
+//!
+//! ### Logs with a shared version header
+//!
+//! In an append-only log, repeating the version header in every record wastes space.
+//! [`VersionedLogWriter`] writes a single header before the first record, and only writes a new one if the versions change, e.g. after the program was upgraded.
+//! [`VersionedLogReader`] deserializes every record using the last header before it.
+//!
+//! ```rust
+//! # use serde_migrate::{versioned, Json, VersionedLogWriter, VersionedLogReader};
+//!
+//! #[versioned]
+//! #[derive(PartialEq, Debug)]
+//! struct Event {
+//!    pub id: u32,
+//! }
+//!
+//! fn main() {
+//!     let mut log = VersionedLogWriter::<_, Json>::new(Vec::new());
+//!     log.append(&Event { id: 1 }).unwrap();
+//!     log.append(&Event { id: 2 }).unwrap();
+//!     let log = log.into_inner();
+//!     assert_eq!(String::from_utf8(log.clone()).unwrap(), r#"{"Header":{"rust_out::Event":1}}
+//! {"Record":{"id":1}}
+//! {"Record":{"id":2}}
+//! "#);
+//!
+//!     let events: Vec<Event> = VersionedLogReader::<_, _, Json>::new(&log[..]).map(Result::unwrap).collect();
+//!     assert_eq!(events, vec![Event { id: 1 }, Event { id: 2 }]);
+//! }
+//! ```
+//!
+//! ### Storing the version header separately
+//!
+//! If the version header has to be stored out-of-band, e.g. in a separate database column or in the header of a wire protocol,
+//! you can use [`serialize_with_versions`] and [`deserialize_with_versions`] instead of [`Versioned`].
+//! The payload is then serialized without any envelope.
+//!
+//! ```rust
+//! # use serde_migrate::{versioned, VersionMap};
+//!
+//! #[versioned]
+//! #[derive(PartialEq, Debug)]
+//! struct MyStruct {
+//!    pub value: u32,
+//! }
+//!
+//! fn main() {
+//!   let mut payload = Vec::new();
+//!   let (versions, _) = serde_migrate::serialize_with_versions(&MyStruct { value: 123 }, &mut serde_json::Serializer::new(&mut payload)).unwrap();
+//!   assert_eq!(payload, br#"{"value":123}"#);
+//!   assert_eq!(serde_json::to_string(&versions).unwrap(), r#"{"rust_out::MyStruct":1}"#);
+//!
+//!   let decoded: MyStruct = serde_migrate::deserialize_with_versions(&versions, &mut serde_json::Deserializer::from_slice(&payload)).unwrap();
+//!   assert_eq!(decoded, MyStruct { value: 123 });
+//! }
+//! ```
+//!
+//! ### Reading the legacy envelope
+//!
+//! Earlier versions of this crate stored a single version for the root type, as `{ "version": 1, "value": { ... } }`.
+//! [`Versioned`] still accepts this envelope in self-describing formats like json, and applies the version to the root type.
+//! Nested types are treated as version 1. Data is always written in the current format.
+//!
+//! ```rust
+//! # use serde_migrate::{versioned, Versioned};
+//!
+//! #[versioned]
+//! #[derive(PartialEq, Debug)]
+//! struct MyStruct {
+//!     #[version(end = 2)]
+//!     pub a: u32,
+//!     #[version(start = 2)]
+//!     pub b: u32,
+//! }
+//!
+//! impl mystruct_migrations::Migrate for MyStruct {
+//!     fn to_v2(v: mystruct_migrations::MyStructV1) -> mystruct_migrations::MyStructV2 {
+//!         mystruct_migrations::MyStructV2 { b: v.a }
+//!     }
+//! }
+//!
+//! fn main() {
+//!     let decoded: MyStruct = serde_json::from_str::<Versioned<_>>(r#"{ "version": 1, "value": { "a": 123 } }"#).unwrap().0;
+//!     assert_eq!(decoded, MyStruct { b: 123 });
+//!     let decoded: MyStruct = serde_json::from_str::<Versioned<_>>(r#"{ "version": 2, "value": { "b": 123 } }"#).unwrap().0;
+//!     assert_eq!(decoded, MyStruct { b: 123 });
+//! }
+//! ```
+//!
 //! ### Keeping compatibility from the start
 //!
 //! If you add the #[macro@versioned] attribute without any version specifiers, the struct will be considered to be version 1.
@@ -151,6 +591,214 @@
 //! }
 //! ```
 //!
+//! ### Preserving unknown fields
+//!
+//! If an older binary reads data written by a newer binary, the fields it does not know about are normally lost when it writes the data back.
+//! With `#[versioned(preserve_unknown)]`, those fields are captured in a hidden `unknown_fields` field of type [`UnknownFields`], and written back when serializing.
+//! The version header keeps the newer version number, so the data is not downgraded.
+//!
+//! Since the fields are captured without knowing their types, they are only captured in human-readable formats like json.
+//! In other formats like bincode, data at the latest version is read and written as usual, and writing a value with unknown fields fails.
+//! When constructing the struct yourself, set `unknown_fields` to `Default::default()`.
+//!
+//! ```rust
+//! # use serde_migrate::{versioned, Versioned};
+//!
+//! #[versioned(preserve_unknown)]
+//! struct MyStruct {
+//!    pub value: u32,
+//! }
+//!
+//! fn main() {
+//!   // Data written by a newer binary, in which MyStruct is at version 2 and has another field.
+//!   let mut decoded: MyStruct = serde_json::from_str::<Versioned<_>>(r#"{"versions":{"rust_out::MyStruct":2},"value":{"value":123,"added":"hello"}}"#).unwrap().0;
+//!   decoded.value = 456;
+//!   let encoded = serde_json::to_string(&Versioned(&decoded)).unwrap();
+//!   assert_eq!(encoded, r#"{"versions":{"rust_out::MyStruct":2},"value":{"value":456,"added":"hello"}}"#);
+//! }
+//! ```
+//!
+//! ### Dropping old versions
+//!
+//! Over time, a struct can accumulate a lot of removed fields and migrations. Once all stored data has been migrated past a certain version,
+//! you can set `#[versioned(min_supported = x)]` and delete all fields that were removed before version x, along with their `to_vN` functions.
+//! Fields that were added before version x can drop their `start` attribute.
+//! Only the versioned structs from version x and onwards are generated, and deserializing older data fails with a "version too old" error.
+//!
+//! Use [`migrate_to_latest`] to rewrite stored data at the latest version before raising the minimum supported version.
+//!
+//! ```rust
+//! # use serde_migrate::{versioned, Versioned};
+//!
+//! // Previously, field `a` was removed in version 3, and field `b` was added in version 2.
+//! #[versioned(min_supported = 3)]
+//! #[derive(PartialEq, Debug)]
+//! struct MyStruct {
+//!     pub b: u32,
+//!     #[version(start = 4)]
+//!     pub c: u32,
+//! }
+//!
+//! impl mystruct_migrations::Migrate for MyStruct {
+//!     fn to_v4(v: mystruct_migrations::MyStructV3) -> mystruct_migrations::MyStructV4 {
+//!         mystruct_migrations::MyStructV4 {
+//!             b: v.b,
+//!             c: v.b * 2,
+//!         }
+//!     }
+//! }
+//!
+//! fn main() {
+//!     let decoded: MyStruct = serde_json::from_str::<Versioned<_>>(r#"{ "versions": { "rust_out::MyStruct": 3 }, "value": { "b": 1 } }"#).unwrap().0;
+//!     assert_eq!(decoded, MyStruct { b: 1, c: 2 });
+//!
+//!     let too_old = serde_json::from_str::<Versioned<MyStruct>>(r#"{ "versions": { "rust_out::MyStruct": 2 }, "value": { "a": 1, "b": 1 } }"#);
+//!     assert!(too_old.is_err());
+//! }
+//! ```
+//!
+//! ### Testing migrations with fixtures
+//!
+//! With the `testing` feature, `testing::assert_migrates` checks that data stored with every version still loads.
+//! It reads fixtures for every version in every enabled format from a directory, migrates them, and compares the result with the expected value stored next to them.
+//! Run the test with `SERDE_MIGRATE_BLESS=1` after adding a version, to store fixtures for the new version from a sample value.
+//!
+//! ```rust,ignore
+//! #[test]
+//! fn old_data_still_loads() {
+//!     let sample = MyStruct { b: 1, c: 2 };
+//!     serde_migrate::testing::assert_migrates(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/my_struct"), &sample);
+//! }
+//! ```
+//!
+//! ### Fuzzing migrations
+//!
+//! With the `arbitrary` feature, `#[versioned(arbitrary)]` derives `arbitrary::Arbitrary` for every version of a type, so that random data of old versions can be generated.
+//! [`assert_random_migrations`] generates random values of every version, serializes them with the right version header, and checks that they deserialize and migrate without panicking.
+//! [`assert_arbitrary_migrates`] does the same for a single value, and can be called from a `cargo fuzz` target.
+//! All fields must implement `arbitrary::Arbitrary` and `Serialize`, so fields of type [`Stored`] are not supported.
+//!
+//! ```rust
+//! # #[cfg(feature = "arbitrary")]
+//! # mod example {
+//! # use serde_migrate::{versioned, Json};
+//! #[versioned(arbitrary)]
+//! struct MyStruct {
+//!     #[version(end = 2)]
+//!     pub a: u32,
+//!     #[version(start = 2)]
+//!     pub b: String,
+//! }
+//!
+//! impl mystruct_migrations::Migrate for MyStruct {
+//!     fn to_v2(v: mystruct_migrations::MyStructV1) -> mystruct_migrations::MyStructV2 {
+//!         mystruct_migrations::MyStructV2 { b: v.a.to_string() }
+//!     }
+//! }
+//!
+//! pub fn main() {
+//!     let migrated = serde_migrate::assert_random_migrations::<MyStruct, Json>(100);
+//!     assert!(migrated > 0);
+//! }
+//! # }
+//! # fn main() {
+//! #     #[cfg(feature = "arbitrary")]
+//! #     example::main();
+//! # }
+//! ```
+//!
+//! ### Other serde formats
+//!
+//! [`Versioned`] works with any serde format, and is tested with CBOR, MessagePack (with structs as arrays or maps), RON, TOML and YAML in addition to the built-in formats.
+//! In formats where structs are maps, the header may come after the value, e.g. in a json value with sorted keys. The value is then buffered until the header has been read,
+//! which only works in self-describing formats. To use helpers like [`upgrade`] and [`RecordReader`] with another format, implement [`Format`] for it.
+//!
+//! ### Reading untrusted data
+//!
+//! Version headers are checked against [`HeaderLimits`] while they are read: by default, at most 1024 types with names of up to 1024 bytes.
+//! Headers that list a type more than once are rejected. The limits can be changed for a scope on the current thread.
+//! The `fuzz` directory contains a `cargo fuzz` target which checks that no input panics the reader.
+//!
+//! ```rust
+//! # use serde_migrate::{versioned, HeaderLimits, Versioned};
+//! #[versioned]
+//! struct MyStruct {
+//!     pub a: u32,
+//! }
+//!
+//! fn main() {
+//!     let json = r#"{ "versions": { "rust_out::MyStruct": 1, "other": 1 }, "value": { "a": 1 } }"#;
+//!     let limits = HeaderLimits { max_entries: 1, ..HeaderLimits::DEFAULT };
+//!     assert!(limits.scope(|| serde_json::from_str::<Versioned<MyStruct>>(json)).is_err());
+//! }
+//! ```
+//!
+//! ### Embedded key-value stores
+//!
+//! With the `redb` feature, [`VersionedTable`] stores values in a [`redb`] table. Values are migrated when they are read, and written back if any migration ran,
+//! so that every value is only migrated once. The write-back only happens if the stored value was not changed by another writer in the meantime.
+//! [`VersionedTable::upgrade_all`] upgrades the values that are never read, in batches of separate transactions.
+//!
+//! ```rust
+//! # #[cfg(feature = "redb")]
+//! # mod example {
+//! # use serde_migrate::{versioned, redb::Database, Json, VersionedTable};
+//! #[versioned]
+//! struct MyStruct {
+//!     pub a: u32,
+//! }
+//!
+//! pub fn main() {
+//!     let dir = tempfile::tempdir().unwrap();
+//!     let db = Database::create(dir.path().join("data.redb")).unwrap();
+//!     let table = VersionedTable::<MyStruct, Json>::new(&db, "my_structs");
+//!     table.insert("key", &MyStruct { a: 1 }).unwrap();
+//!     assert_eq!(table.get("key").unwrap().unwrap().a, 1);
+//!
+//!     let report = table.upgrade_all(100).unwrap();
+//!     assert_eq!(report.records, 1);
+//!     assert_eq!(report.upgraded, 0);
+//! }
+//! # }
+//! # fn main() {
+//! #     #[cfg(feature = "redb")]
+//! #     example::main();
+//! # }
+//! ```
+//!
+//! ### SQLite columns
+//!
+//! With the `rusqlite` feature, [`Versioned`] implements `ToSql` and `FromSql`. Values are written as json text, and can be read from text or blob columns.
+//! [`upgrade_sqlite_column`] upgrades every value in a column in a single transaction, and only rewrites the rows whose version header is out of date.
+//! It works with any [`Format`], e.g. with [`Bincode`] for blob columns.
+//!
+//! ```rust
+//! # #[cfg(feature = "rusqlite")]
+//! # mod example {
+//! # use serde_migrate::{versioned, rusqlite::Connection, upgrade_sqlite_column, Json, Versioned};
+//! #[versioned]
+//! struct MyStruct {
+//!     pub a: u32,
+//! }
+//!
+//! pub fn main() {
+//!     let mut conn = Connection::open_in_memory().unwrap();
+//!     conn.execute("CREATE TABLE my_structs (id INTEGER PRIMARY KEY, data TEXT)", ()).unwrap();
+//!     conn.execute("INSERT INTO my_structs (data) VALUES (?1)", (Versioned(MyStruct { a: 1 }),)).unwrap();
+//!     let value: Versioned<MyStruct> = conn.query_row("SELECT data FROM my_structs", (), |row| row.get(0)).unwrap();
+//!     assert_eq!(value.0.a, 1);
+//!
+//!     let report = upgrade_sqlite_column::<MyStruct, Json>(&mut conn, "my_structs", "data").unwrap();
+//!     assert_eq!(report.records, 1);
+//!     assert_eq!(report.upgraded, 0);
+//! }
+//! # }
+//! # fn main() {
+//! #     #[cfg(feature = "rusqlite")]
+//! #     example::main();
+//! # }
+//! ```
+//!
 //! ## What counts as a breaking change?
 //!
 //! What exactly counts as a breaking change differs by serialization format. For example, in json, keys can be re-ordered without breaking compatibility, but
@@ -165,24 +813,131 @@
 //! * Changing the order of fields (only breaking in non-self-describing formats like bincode, but not in e.g. json)
 //! * Changing the name of a type or module (this may cause this crate to not be able to find the version information) (TODO: Implement an alias attribute to help with this)
 //! * Adding/removing serde attributes like `serialize_with` or `skip_serializing_if`.
+//!
+//! ### Detecting breaking changes
+//!
+//! The #[macro@versioned] macro computes a fingerprint of every version, from the names, order and types of the fields and their serde attributes.
+//! Commit a lock file with these fingerprints, and check it in a test using [`SchemaLock`].
+//! The test fails if the shape of a version in the lock file changes, or if a version is not in the lock file yet.
+//!
+//! ```rust,no_run
+//! # use serde_migrate::{versioned, SchemaLock};
+//! #[versioned]
+//! struct MyStruct {
+//!     pub a: u32,
+//! }
+//!
+//! #[test]
+//! fn schema_lock() {
+//!     SchemaLock::new()
+//!         .with::<MyStruct>()
+//!         .check_file(concat!(env!("CARGO_MANIFEST_DIR"), "/schema.lock"))
+//!         .unwrap();
+//! }
+//! # fn main() {}
+//! ```
+//!
+//! Run the test with `SERDE_MIGRATE_UPDATE_LOCK=1` to create the lock file, to add new versions, or to update a version whose change is intended, e.g. because it was never released.
 use std::{fmt::Display, any::TypeId, cell::RefCell};
 
 pub use serde_migrate_macros::versioned;
 
-use std::collections::HashMap;
+mod compact;
+mod document;
+mod format;
+#[cfg(feature = "arbitrary")]
+mod fuzz;
+mod info;
+mod limits;
+mod lock;
+mod log;
+mod peek;
+mod registry;
+#[cfg(feature = "schemars")]
+mod schema;
+mod sidecar;
+#[cfg(feature = "rusqlite")]
+mod sqlite;
+mod stored;
+#[cfg(feature = "redb")]
+mod store;
+mod stream;
+#[cfg(feature = "testing")]
+pub mod testing;
+mod unknown;
+mod upgrade;
+#[cfg(feature = "json")]
+mod value;
+pub use compact::CompactVersioned;
+pub use document::{VersionedDocument, Schema, SchemaDocument, SchemaMigration};
+pub use format::{Format, FormatError};
+#[cfg(feature = "arbitrary")]
+pub use fuzz::{ArbitraryVersions, assert_arbitrary_migrates, assert_random_migrations};
+#[cfg(feature = "arbitrary")]
+#[doc(hidden)]
+pub use fuzz::arbitrary_version_bytes;
+#[cfg(feature = "arbitrary")]
+pub use arbitrary;
+#[cfg(feature = "json")]
+pub use format::Json;
+#[cfg(feature = "bincode")]
+pub use format::Bincode;
+#[cfg(feature = "postcard")]
+pub use format::Postcard;
+pub use info::{VersionedType, VersionInfo, FieldInfo, Changelog, ChangelogEntry, FieldChange};
+pub use limits::HeaderLimits;
+pub(crate) use limits::HeaderSeed;
+pub use lock::{SchemaFingerprints, SchemaLock, SchemaChange, SchemaLockError, UPDATE_LOCK_ENV};
+pub use log::{VersionedLogWriter, VersionedLogReader};
+pub use peek::peek_versions;
+pub use registry::MigrationRegistry;
+#[cfg(feature = "schemars")]
+pub use schemars;
+pub use sidecar::{VersionMap, versions_of, serialize_with_versions, deserialize_with_versions};
+pub use stored::{Stored, MigrateStored, DeserializeStored};
+#[cfg(feature = "redb")]
+pub use store::{VersionedTable, StoreError};
+#[cfg(feature = "redb")]
+pub use redb;
+#[cfg(feature = "rusqlite")]
+pub use rusqlite;
+#[cfg(feature = "rusqlite")]
+pub use sqlite::upgrade_sqlite_column;
+pub use stream::{RecordReader, RecordError, StreamReport, upgrade_records};
+pub use unknown::{UnknownFields, UnknownValue, UnknownValueDeserializer};
+pub(crate) use unknown::BufferSeed;
+pub use upgrade::{migrate_to_latest, upgrade, upgrade_dry_run, UpgradeReport};
+#[cfg(any(feature = "redb", feature = "rusqlite"))]
+pub use upgrade::TableReport;
+#[cfg(feature = "json")]
+pub use value::MigrateValue;
+
+use std::collections::{HashMap, BTreeMap};
 
 use serde::{Serialize, Serializer, ser::{self, SerializeStruct}, Deserialize, de::{Visitor, SeqAccess}, Deserializer};
 
 thread_local! {
-    pub static DESERIALIZATION_STATE: std::cell::RefCell<Option<DeserializationState>> = RefCell::new(None);
+    pub static DESERIALIZATION_STATE: std::cell::RefCell<Option<DeserializationState>> = const { RefCell::new(None) };
 }
 
+#[non_exhaustive]
 pub struct DeserializationState {
     pub versions: HashMap<TypeId, u32>,
     pub remaining_versions: HashMap<String, u32>,
+    /// Versions from a compact header, keyed by the numeric id of the type. See [`CompactVersioned`].
+    pub remaining_ids: HashMap<u16, u32>,
 }
 
 impl DeserializationState {
+    /// Creates a state with the given versions by type, and by type name.
+    pub fn new(versions: HashMap<TypeId, u32>, remaining_versions: HashMap<String, u32>) -> Self {
+        DeserializationState {
+            versions,
+            remaining_versions,
+            remaining_ids: Default::default(),
+        }
+    }
+
     pub fn get_version<'de, T: 'static, D: Deserializer<'de>> (&mut self) -> Result<u32, D::Error> {
         if let Some(v) = self.versions.get(&std::any::TypeId::of::<T>()) {
             Ok(*v)
@@ -199,6 +954,52 @@ impl DeserializationState {
             // Err(serde::de::Error::custom(format!("no version found for type {}", std::any::type_name::<T>())))
         }
     }
+
+    /// Like [`DeserializationState::get_version`], but for types with a numeric id, which may be stored in a compact header.
+    pub fn get_version_with_id<'de, T: 'static, D: Deserializer<'de>> (&mut self, id: u16) -> Result<u32, D::Error> {
+        if let Some(v) = self.versions.get(&std::any::TypeId::of::<T>()) {
+            Ok(*v)
+        } else if let Some(v) = self.remaining_ids.get(&id) {
+            self.versions.insert(std::any::TypeId::of::<T>(), *v);
+            Ok(*v)
+        } else {
+            self.get_version::<T, D>()
+        }
+    }
+}
+
+/// Runs `f` with the given per-type versions as the deserialization state, and restores the previous state afterwards.
+pub(crate) fn with_versions<R>(versions: HashMap<String, u32>, f: impl FnOnce() -> R) -> R {
+    with_state(DeserializationState {
+        versions: Default::default(),
+        remaining_versions: versions,
+        remaining_ids: Default::default(),
+    }, f)
+}
+
+/// Runs `f` with the given deserialization state, and restores the previous state afterwards.
+pub(crate) fn with_state<R>(new_state: DeserializationState, f: impl FnOnce() -> R) -> R {
+    let prev = DESERIALIZATION_STATE.with(|state| {
+        state.replace(Some(new_state))
+    });
+    let result = f();
+    DESERIALIZATION_STATE.with(|state| {
+        state.replace(prev);
+    });
+    result
+}
+
+/// Like [`with_state`], but moves the state back into `reused` afterwards, so that it can be used for many values.
+pub(crate) fn with_reused_state<R>(reused: &mut DeserializationState, f: impl FnOnce() -> R) -> R {
+    let new_state = std::mem::replace(reused, DeserializationState::new(Default::default(), Default::default()));
+    let prev = DESERIALIZATION_STATE.with(|state| {
+        state.replace(Some(new_state))
+    });
+    let result = f();
+    if let Some(state) = DESERIALIZATION_STATE.with(|state| state.replace(prev)) {
+        *reused = state;
+    }
+    result
 }
 
 pub struct Versioned<T>(pub T);
@@ -231,7 +1032,7 @@ impl<T> Default for VersionedVisitor<T> {
 
 #[derive(serde::Deserialize)]
 #[serde(field_identifier, rename_all = "lowercase")]
-enum VersionedField { Versions, Value }
+pub(crate) enum VersionedField { Versions, Value, Version }
 
 impl<'de, T: Deserialize<'de>> Visitor<'de> for VersionedVisitor<T> {
     fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
@@ -244,12 +1045,13 @@ impl<'de, T: Deserialize<'de>> Visitor<'de> for VersionedVisitor<T> {
     where
         V: SeqAccess<'de>,
     {
-        let versions: HashMap<String, u32> = seq.next_element()?
+        let versions = seq.next_element_seed(HeaderSeed)?
             .ok_or_else(|| serde::de::Error::invalid_length(0, &self))?;
         let prev = DESERIALIZATION_STATE.with(|state| {
             state.replace(Some(DeserializationState {
                 versions: Default::default(),
                 remaining_versions: versions,
+                remaining_ids: Default::default(),
             }))
         });
         let value = seq.next_element();
@@ -264,41 +1066,40 @@ impl<'de, T: Deserialize<'de>> Visitor<'de> for VersionedVisitor<T> {
     where
         A: serde::de::MapAccess<'de>,
     {
-        let mut version_found = false;
-        let mut prev = None;
+        let mut versions = None;
+        // Some formats do not keep the order of the fields, e.g. a toml table or a json value with sorted keys.
+        // If the value comes before the header, it is buffered until the header has been read.
+        // The value is deserialized from the buffer as human-readable or not, like the format it was read from.
+        let mut buffered: Option<(UnknownValue, bool)> = None;
         while let Some(key) = map.next_key()? {
             match key {
-                VersionedField::Versions => {
-                    if version_found {
-                        return Err(serde::de::Error::duplicate_field("versions"));
+                VersionedField::Versions | VersionedField::Version => {
+                    if versions.is_some() {
+                        return Err(serde::de::Error::custom("Duplicate field 'versions'. When deserializing a Versioned<T>, only one of the fields 'versions' and 'version' may be present."));
                     }
-                    version_found = true;
-                    let versions: HashMap<String, u32> = map.next_value()?;
-                    DESERIALIZATION_STATE.with(|state| {
-                        prev = state.replace(Some(DeserializationState {
-                            versions: Default::default(),
-                            remaining_versions: versions,
-                        }));
-                    });
+                    let header = if matches!(key, VersionedField::Versions) {
+                        map.next_value_seed(HeaderSeed)?
+                    } else {
+                        // Legacy envelope, with a single version that applies to the root type.
+                        let version: u32 = map.next_value()?;
+                        HeaderLimits::current().check_version(version)?;
+                        HashMap::from([(std::any::type_name::<T>().to_owned(), version)])
+                    };
+                    if let Some((value, is_human_readable)) = buffered.take() {
+                        return with_versions(header, || T::deserialize(UnknownValueDeserializer::new(value, is_human_readable))).map(Versioned);
+                    }
+                    versions = Some(header);
                 }
                 VersionedField::Value => {
-                    if !version_found {
-                        return Err(serde::de::Error::custom("Missing field 'versions'. When deserializing a Versioned<T>, the field 'versions' must be present before the field 'value'."));
+                    match versions.take() {
+                        Some(header) => return with_versions(header, || map.next_value()).map(Versioned),
+                        None if buffered.is_some() => return Err(serde::de::Error::duplicate_field("value")),
+                        None => buffered = Some(map.next_value_seed(BufferSeed)?),
                     }
-                    let value = map.next_value();
-                    DESERIALIZATION_STATE.with(|state| {
-                        state.replace(prev);
-                    });
-                    return Ok(Versioned(value?));
                 }
             }
         }
-        if version_found {
-            DESERIALIZATION_STATE.with(|state| {
-                state.replace(prev);
-            });
-        }
-        Err(serde::de::Error::missing_field("value"))
+        Err(serde::de::Error::missing_field(if buffered.is_some() { "versions" } else { "value" }))
     }
 }
 
@@ -317,6 +1118,8 @@ impl<'de, T: Deserialize<'de>> Deserialize<'de> for Versioned<T> {
 pub struct VersionSerializer {
     pub seen: Vec<TypeId>,
     pub versions: Vec<(&'static str, u32)>,
+    /// Numeric ids of the types in `versions` which have one.
+    pub ids: Vec<(&'static str, u16)>,
     pub last: Option<TypeId>,
 }
 
@@ -333,7 +1136,16 @@ impl VersionSerializer {
         }
     }
 
-    pub fn to_serialized_versions(self) -> HashMap<&'static str, u32> {
+    pub fn set_version_with_id<T: 'static>(&mut self, version: u32, id: u16) {
+        let len = self.versions.len();
+        self.set_version::<T>(version);
+        if self.versions.len() > len {
+            self.ids.push((std::any::type_name::<T>(), id));
+        }
+    }
+
+    /// The versions of all types that were seen, sorted by type name so that the header is always serialized in the same order.
+    pub fn to_serialized_versions(self) -> BTreeMap<&'static str, u32> {
         self.versions.into_iter().collect()
     }
 }
@@ -373,7 +1185,7 @@ impl serde::ser::Error for DummyError {
 
 type SerializationResult = Result<(), DummyError>;
 
-impl<'a> serde::ser::Serializer for &'a mut VersionSerializer {
+impl serde::ser::Serializer for &mut VersionSerializer {
     fn is_human_readable(&self) -> bool {
         false
     }
@@ -477,9 +1289,9 @@ impl<'a> serde::ser::Serializer for &'a mut VersionSerializer {
     }
 
     #[inline]
-    fn serialize_some<T: ?Sized>(self, value: &T) -> Result<Self::Ok, Self::Error>
+    fn serialize_some<T>(self, value: &T) -> Result<Self::Ok, Self::Error>
     where
-        T: serde::Serialize {
+        T: ?Sized + serde::Serialize {
             value.serialize(self)
     }
 
@@ -504,18 +1316,18 @@ impl<'a> serde::ser::Serializer for &'a mut VersionSerializer {
     }
 
     #[inline]
-    fn serialize_newtype_struct<T: ?Sized>(
+    fn serialize_newtype_struct<T>(
         self,
         _name: &'static str,
         value: &T,
     ) -> Result<Self::Ok, Self::Error>
     where
-        T: serde::Serialize {
+        T: ?Sized + serde::Serialize {
             value.serialize(self)
     }
 
     #[inline]
-    fn serialize_newtype_variant<T: ?Sized>(
+    fn serialize_newtype_variant<T>(
         self,
         _name: &'static str,
         _variant_index: u32,
@@ -523,7 +1335,7 @@ impl<'a> serde::ser::Serializer for &'a mut VersionSerializer {
         value: &T,
     ) -> Result<Self::Ok, Self::Error>
     where
-        T: serde::Serialize {
+        T: ?Sized + serde::Serialize {
             value.serialize(self)
     }
 
@@ -583,7 +1395,7 @@ impl<'a> serde::ser::Serializer for &'a mut VersionSerializer {
     }
 }
 
-impl<'a> ser::SerializeSeq for &'a mut VersionSerializer {
+impl ser::SerializeSeq for &mut VersionSerializer {
     // Must match the `Ok` type of the serializer.
     type Ok = ();
     // Must match the `Error` type of the serializer.
@@ -604,7 +1416,7 @@ impl<'a> ser::SerializeSeq for &'a mut VersionSerializer {
     }
 }
 
-impl<'a> ser::SerializeTuple for &'a mut VersionSerializer {
+impl ser::SerializeTuple for &mut VersionSerializer {
     type Ok = ();
     type Error = DummyError;
 
@@ -622,7 +1434,7 @@ impl<'a> ser::SerializeTuple for &'a mut VersionSerializer {
     }
 }
 
-impl<'a> ser::SerializeTupleStruct for &'a mut VersionSerializer {
+impl ser::SerializeTupleStruct for &mut VersionSerializer {
     type Ok = ();
     type Error = DummyError;
 
@@ -641,7 +1453,7 @@ impl<'a> ser::SerializeTupleStruct for &'a mut VersionSerializer {
 }
 
 
-impl<'a> ser::SerializeTupleVariant for &'a mut VersionSerializer {
+impl ser::SerializeTupleVariant for &mut VersionSerializer {
     type Ok = ();
     type Error = DummyError;
 
@@ -659,7 +1471,7 @@ impl<'a> ser::SerializeTupleVariant for &'a mut VersionSerializer {
     }
 }
 
-impl<'a> ser::SerializeMap for &'a mut VersionSerializer {
+impl ser::SerializeMap for &mut VersionSerializer {
     type Ok = ();
     type Error = DummyError;
 
@@ -685,7 +1497,7 @@ impl<'a> ser::SerializeMap for &'a mut VersionSerializer {
     }
 }
 
-impl<'a> ser::SerializeStruct for &'a mut VersionSerializer {
+impl ser::SerializeStruct for &mut VersionSerializer {
     type Ok = ();
     type Error = DummyError;
 
@@ -703,7 +1515,7 @@ impl<'a> ser::SerializeStruct for &'a mut VersionSerializer {
     }
 }
 
-impl<'a> ser::SerializeStructVariant for &'a mut VersionSerializer {
+impl ser::SerializeStructVariant for &mut VersionSerializer {
     type Ok = ();
     type Error = DummyError;
 
@@ -0,0 +1,160 @@
+use std::{cell::Cell, collections::HashMap};
+
+use serde::{Deserializer, de::{self, DeserializeSeed, MapAccess, Visitor}};
+
+thread_local! {
+    static LIMITS: Cell<HeaderLimits> = const { Cell::new(HeaderLimits::DEFAULT) };
+}
+
+/// Limits on the version headers read by [`crate::Versioned`], [`crate::CompactVersioned`], [`crate::VersionedDocument`], [`crate::VersionedLogReader`] and [`crate::peek_versions`].
+///
+/// Data from untrusted sources can contain a header with a huge number of entries, or very long type names.
+/// Headers exceeding the limits are rejected while they are read, before they are stored in memory.
+/// Headers with the same type more than once are always rejected.
+///
+/// The limits apply to the current thread, and can be changed for a scope with [`HeaderLimits::scope`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HeaderLimits {
+    /// The maximum number of types in a header.
+    pub max_entries: usize,
+    /// The maximum length of a type name in a header, in bytes.
+    pub max_key_length: usize,
+    /// The highest version number accepted in a header.
+    pub max_version: u32,
+}
+
+impl HeaderLimits {
+    /// The limits used unless others are set: 1024 types with names of up to 1024 bytes, and any version number.
+    pub const DEFAULT: HeaderLimits = HeaderLimits {
+        max_entries: 1024,
+        max_key_length: 1024,
+        max_version: u32::MAX,
+    };
+
+    /// No limits, except that duplicate types are still rejected.
+    pub const UNLIMITED: HeaderLimits = HeaderLimits {
+        max_entries: usize::MAX,
+        max_key_length: usize::MAX,
+        max_version: u32::MAX,
+    };
+
+    /// The limits in effect on the current thread.
+    pub fn current() -> HeaderLimits {
+        LIMITS.with(Cell::get)
+    }
+
+    /// Runs `f` with these limits on the current thread, and restores the previous limits afterwards, even if `f` panics.
+    pub fn scope<R>(self, f: impl FnOnce() -> R) -> R {
+        let _restore = RestoreLimits(LIMITS.with(|limits| limits.replace(self)));
+        f()
+    }
+
+    pub(crate) fn check_entries<E: de::Error>(&self, entries: usize) -> Result<(), E> {
+        if entries > self.max_entries {
+            return Err(E::custom(format!("The version header has more than {} entries", self.max_entries)));
+        }
+        Ok(())
+    }
+
+    pub(crate) fn check_version<E: de::Error>(&self, version: u32) -> Result<(), E> {
+        if version > self.max_version {
+            return Err(E::custom(format!("Version {} in the version header is higher than the limit of {}", version, self.max_version)));
+        }
+        Ok(())
+    }
+}
+
+/// Restores the previous limits when a [`HeaderLimits::scope`] ends.
+struct RestoreLimits(HeaderLimits);
+
+impl Drop for RestoreLimits {
+    fn drop(&mut self) {
+        LIMITS.with(|limits| limits.set(self.0));
+    }
+}
+
+impl Default for HeaderLimits {
+    fn default() -> Self {
+        HeaderLimits::DEFAULT
+    }
+}
+
+/// Deserializes a version header, as a map from type names to versions, within the current [`HeaderLimits`].
+pub(crate) struct HeaderSeed;
+
+impl<'de> DeserializeSeed<'de> for HeaderSeed {
+    type Value = HashMap<String, u32>;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_map(HeaderVisitor { limits: HeaderLimits::current() })
+    }
+}
+
+struct HeaderVisitor {
+    limits: HeaderLimits,
+}
+
+impl<'de> Visitor<'de> for HeaderVisitor {
+    type Value = HashMap<String, u32>;
+
+    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        formatter.write_str("a map from type names to versions")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        if let Some(len) = map.size_hint() {
+            self.limits.check_entries(len)?;
+        }
+        let mut versions = HashMap::new();
+        while let Some(name) = map.next_key_seed(KeySeed { max_length: self.limits.max_key_length })? {
+            self.limits.check_entries(versions.len() + 1)?;
+            let version: u32 = map.next_value()?;
+            self.limits.check_version(version)?;
+            if versions.contains_key(&name) {
+                return Err(de::Error::custom(format!("Duplicate type {} in the version header", name)));
+            }
+            versions.insert(name, version);
+        }
+        Ok(versions)
+    }
+}
+
+/// Deserializes a type name, and rejects it before copying it if it is too long.
+struct KeySeed {
+    max_length: usize,
+}
+
+impl KeySeed {
+    fn check<E: de::Error>(&self, name: &str) -> Result<(), E> {
+        if name.len() > self.max_length {
+            return Err(E::custom(format!("A type name in the version header is longer than {} bytes", self.max_length)));
+        }
+        Ok(())
+    }
+}
+
+impl<'de> DeserializeSeed<'de> for KeySeed {
+    type Value = String;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_str(self)
+    }
+}
+
+impl<'de> Visitor<'de> for KeySeed {
+    type Value = String;
+
+    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        formatter.write_str("a type name")
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Self::Value, E> {
+        self.check(v)?;
+        Ok(v.to_owned())
+    }
+
+    fn visit_string<E: de::Error>(self, v: String) -> Result<Self::Value, E> {
+        self.check(&v)?;
+        Ok(v)
+    }
+}
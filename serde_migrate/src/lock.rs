@@ -0,0 +1,217 @@
+use std::{collections::BTreeMap, fmt::Display, path::Path};
+
+/// Implemented by the #[macro@crate::versioned] macro.
+///
+/// Describes the shape of every version of a type: the names, order and types of the fields, and their serde attributes.
+/// If the fingerprint of a version changes, data stored with that version may no longer be readable.
+pub trait SchemaFingerprints {
+    /// The fingerprint of every supported version, as `(version, fingerprint)`.
+    const FINGERPRINTS: &'static [(u32, &'static str)];
+}
+
+/// The environment variable which makes [`SchemaLock::check_file`] write the lock file instead of failing.
+pub const UPDATE_LOCK_ENV: &str = "SERDE_MIGRATE_UPDATE_LOCK";
+
+/// The fingerprints of all versions of some versioned types, e.g. as stored in a lock file committed to the repository.
+///
+/// Use [`SchemaLock::check_file`] in a test to make sure that the shape of released versions never changes.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct SchemaLock {
+    types: BTreeMap<String, BTreeMap<u32, String>>,
+}
+
+/// A version whose fingerprint differs from the one in the lock file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SchemaChange {
+    pub type_key: String,
+    pub version: u32,
+    pub locked: String,
+    pub current: String,
+}
+
+#[derive(Debug)]
+pub enum SchemaLockError {
+    Io(std::io::Error),
+    /// The lock file could not be parsed. The line number starts at 1.
+    Parse { line: usize, message: String },
+    /// The shape of some versions changed.
+    Changed(Vec<SchemaChange>),
+    /// Some versions are not in the lock file yet, as `(type key, version)`.
+    Missing(Vec<(String, u32)>),
+}
+
+impl Display for SchemaLockError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SchemaLockError::Io(e) => write!(f, "{}", e),
+            SchemaLockError::Parse { line, message } => write!(f, "Invalid schema lock file on line {}: {}", line, message),
+            SchemaLockError::Changed(changes) => {
+                writeln!(f, "The shape of released versions changed, so stored data may no longer be readable:")?;
+                for change in changes {
+                    writeln!(f, "  {} version {}", change.type_key, change.version)?;
+                    writeln!(f, "    locked:  {}", change.locked)?;
+                    writeln!(f, "    current: {}", change.current)?;
+                }
+                write!(f, "Add a new version instead. If the change is intended, run with {}=1 to update the lock file", UPDATE_LOCK_ENV)
+            }
+            SchemaLockError::Missing(missing) => {
+                writeln!(f, "Some versions are not in the schema lock file:")?;
+                for (type_key, version) in missing {
+                    writeln!(f, "  {} version {}", type_key, version)?;
+                }
+                write!(f, "Run with {}=1 to add them", UPDATE_LOCK_ENV)
+            }
+        }
+    }
+}
+
+impl std::error::Error for SchemaLockError {}
+
+impl From<std::io::Error> for SchemaLockError {
+    fn from(e: std::io::Error) -> Self {
+        SchemaLockError::Io(e)
+    }
+}
+
+impl SchemaLock {
+    pub fn new() -> Self {
+        SchemaLock::default()
+    }
+
+    /// Adds the current fingerprints of a type.
+    pub fn with<T: SchemaFingerprints>(mut self) -> Self {
+        self.insert::<T>();
+        self
+    }
+
+    /// Adds the current fingerprints of a type. Fingerprints of versions that are already in the lock are replaced.
+    pub fn insert<T: SchemaFingerprints>(&mut self) {
+        let versions = self.types.entry(std::any::type_name::<T>().to_owned()).or_default();
+        for (version, fingerprint) in T::FINGERPRINTS {
+            versions.insert(*version, fingerprint.to_string());
+        }
+    }
+
+    pub fn get(&self, type_key: &str, version: u32) -> Option<&str> {
+        self.types.get(type_key)?.get(&version).map(String::as_str)
+    }
+
+    /// Reads a lock file, with one `<type>\t<version>\t<fingerprint>` entry per line. Lines starting with `#` are ignored.
+    ///
+    /// The fields are separated by tabs, since type keys can contain spaces, e.g. `Pair<u32, String>`.
+    pub fn parse(input: &str) -> Result<Self, SchemaLockError> {
+        let mut lock = SchemaLock::default();
+        for (i, line) in input.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let error = |message: &str| SchemaLockError::Parse { line: i + 1, message: message.to_owned() };
+            let mut parts = line.splitn(3, '\t');
+            let (Some(type_key), Some(version), Some(fingerprint)) = (parts.next(), parts.next(), parts.next()) else {
+                return Err(error("Expected '<type>\\t<version>\\t<fingerprint>'"));
+            };
+            let version = version.parse().map_err(|_| error("Invalid version"))?;
+            if lock.types.entry(type_key.to_owned()).or_default().insert(version, fingerprint.to_owned()).is_some() {
+                return Err(error("Duplicate version"));
+            }
+        }
+        Ok(lock)
+    }
+
+    /// Compares the fingerprints with a lock, and returns the versions whose fingerprint changed.
+    ///
+    /// Versions which are not in the lock, e.g. because they were just added, are not changes. Neither are versions which were removed.
+    pub fn changes_since(&self, locked: &SchemaLock) -> Vec<SchemaChange> {
+        let mut changes = vec![];
+        for (type_key, versions) in &self.types {
+            for (version, current) in versions {
+                if let Some(locked) = locked.get(type_key, *version) {
+                    if locked != current {
+                        changes.push(SchemaChange {
+                            type_key: type_key.clone(),
+                            version: *version,
+                            locked: locked.to_owned(),
+                            current: current.clone(),
+                        });
+                    }
+                }
+            }
+        }
+        changes
+    }
+
+    /// Returns the versions which are not in a lock, as `(type key, version)`.
+    pub fn missing_from(&self, locked: &SchemaLock) -> Vec<(String, u32)> {
+        let mut missing = vec![];
+        for (type_key, versions) in &self.types {
+            for version in versions.keys() {
+                if locked.get(type_key, *version).is_none() {
+                    missing.push((type_key.clone(), *version));
+                }
+            }
+        }
+        missing
+    }
+
+    /// Checks that every version is in the lock file at `path`, with the same fingerprint. The file is never written.
+    ///
+    /// If the environment variable [`UPDATE_LOCK_ENV`] is set, this calls [`SchemaLock::update_file`] instead, which adds new versions and updates changed ones.
+    pub fn check_file(&self, path: impl AsRef<Path>) -> Result<(), SchemaLockError> {
+        let update = std::env::var_os(UPDATE_LOCK_ENV).is_some_and(|v| !v.is_empty() && v != "0");
+        if update {
+            return self.update_file(path);
+        }
+        let (_, lock) = Self::read_file(path.as_ref())?;
+        let changes = self.changes_since(&lock);
+        if !changes.is_empty() {
+            return Err(SchemaLockError::Changed(changes));
+        }
+        let missing = self.missing_from(&lock);
+        if !missing.is_empty() {
+            return Err(SchemaLockError::Missing(missing));
+        }
+        Ok(())
+    }
+
+    /// Writes the fingerprints to the lock file at `path`, adding new versions and replacing the fingerprints of changed versions.
+    ///
+    /// The lock file is created if it does not exist. Entries of other types are kept.
+    pub fn update_file(&self, path: impl AsRef<Path>) -> Result<(), SchemaLockError> {
+        let path = path.as_ref();
+        let (existing, mut lock) = Self::read_file(path)?;
+        for (type_key, versions) in &self.types {
+            lock.types.entry(type_key.clone()).or_default().extend(versions.iter().map(|(v, f)| (*v, f.clone())));
+        }
+        let contents = lock.to_string();
+        if existing.as_deref() != Some(contents.as_str()) {
+            std::fs::write(path, contents)?;
+        }
+        Ok(())
+    }
+
+    /// Reads the lock file at `path`, if it exists. A missing file is an empty lock.
+    fn read_file(path: &Path) -> Result<(Option<String>, SchemaLock), SchemaLockError> {
+        match std::fs::read_to_string(path) {
+            Ok(contents) => {
+                let lock = SchemaLock::parse(&contents)?;
+                Ok((Some(contents), lock))
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok((None, SchemaLock::default())),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+impl Display for SchemaLock {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "# The shape of every version of the versioned types, generated by serde_migrate.")?;
+        writeln!(f, "# Released versions must never change, since stored data would no longer be readable.")?;
+        for (type_key, versions) in &self.types {
+            for (version, fingerprint) in versions {
+                writeln!(f, "{}\t{}\t{}", type_key, version, fingerprint)?;
+            }
+        }
+        Ok(())
+    }
+}
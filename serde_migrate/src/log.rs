@@ -0,0 +1,166 @@
+use std::{io::{BufReader, Read, Write}, marker::PhantomData};
+
+use serde::{Serialize, Deserialize, Deserializer, de::{DeserializeOwned, EnumAccess, VariantAccess, Visitor}};
+
+use crate::{DeserializationState, HeaderSeed, Format, FormatError, RecordError, VersionMap, versions_of, with_reused_state};
+
+/// An entry in a versioned log. Every record is deserialized using the versions in the last header before it.
+#[derive(Serialize)]
+enum LogEntry<T> {
+    Header(VersionMap),
+    Record(T),
+}
+
+#[derive(Deserialize)]
+enum LogEntryVariant { Header, Record }
+
+/// Headers are read with [`HeaderSeed`], so that they are checked against the current [`crate::HeaderLimits`].
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for LogEntry<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_enum("LogEntry", &["Header", "Record"], LogEntryVisitor(PhantomData))
+    }
+}
+
+struct LogEntryVisitor<T>(PhantomData<T>);
+
+impl<'de, T: Deserialize<'de>> Visitor<'de> for LogEntryVisitor<T> {
+    type Value = LogEntry<T>;
+
+    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        formatter.write_str("a header or a record")
+    }
+
+    fn visit_enum<A: EnumAccess<'de>>(self, data: A) -> Result<Self::Value, A::Error> {
+        match data.variant()? {
+            (LogEntryVariant::Header, variant) => {
+                let header = variant.newtype_variant_seed(HeaderSeed)?;
+                Ok(LogEntry::Header(header.into_iter().collect()))
+            }
+            (LogEntryVariant::Record, variant) => variant.newtype_variant().map(LogEntry::Record),
+        }
+    }
+}
+
+/// Writes a log of records, where the versions of all types are stored in a single header instead of in every record.
+///
+/// The first record is preceded by a header. A new header is only written when a record contains a type, or a version of a type, which is not in the current header.
+/// Entries are framed as described by [`Format::write_record`]. Use [`VersionedLogReader`] to read the log back.
+pub struct VersionedLogWriter<W, F> {
+    writer: W,
+    header: Option<VersionMap>,
+    _p: PhantomData<F>,
+}
+
+impl<W: Write, F: Format> VersionedLogWriter<W, F> {
+    /// Starts a new log. Nothing is written until the first record is appended.
+    pub fn new(writer: W) -> Self {
+        VersionedLogWriter {
+            writer,
+            header: None,
+            _p: PhantomData,
+        }
+    }
+
+    /// Appends a record to the log, preceded by a new header if necessary.
+    pub fn append<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), FormatError> {
+        let versions = versions_of(value);
+        let up_to_date = self.header.as_ref().is_some_and(|header| {
+            versions.iter().all(|(name, version)| header.get(name) == Some(version))
+        });
+        if !up_to_date {
+            let header = self.header.get_or_insert_with(Default::default);
+            header.extend(versions);
+            F::write_record(&mut self.writer, &F::to_bytes(&LogEntry::<()>::Header(header.clone()))?)?;
+        }
+        F::write_record(&mut self.writer, &F::to_bytes(&LogEntry::Record(value))?)?;
+        Ok(())
+    }
+
+    /// The versions in the last written header.
+    pub fn header(&self) -> Option<&VersionMap> {
+        self.header.as_ref()
+    }
+
+    pub fn flush(&mut self) -> Result<(), FormatError> {
+        Ok(self.writer.flush()?)
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+/// Reads a log written by [`VersionedLogWriter`] one record at a time, and runs all migrations on each record.
+///
+/// Like [`crate::RecordReader`], an error for a single record does not end the iteration, but an error reading the stream itself does.
+/// Headers are not returned as records, and are not counted in [`RecordError::index`].
+pub struct VersionedLogReader<R, T, F> {
+    reader: BufReader<R>,
+    buf: Vec<u8>,
+    header: Option<VersionMap>,
+    /// The state for deserializing records with the versions in `header`, built once per header.
+    state: DeserializationState,
+    index: usize,
+    done: bool,
+    _p: PhantomData<(T, F)>,
+}
+
+impl<R: Read, T: DeserializeOwned, F: Format> VersionedLogReader<R, T, F> {
+    pub fn new(reader: R) -> Self {
+        VersionedLogReader {
+            reader: BufReader::new(reader),
+            buf: Vec::new(),
+            header: None,
+            state: DeserializationState::new(Default::default(), Default::default()),
+            index: 0,
+            done: false,
+            _p: PhantomData,
+        }
+    }
+
+    /// The versions in the last header that was read.
+    pub fn header(&self) -> Option<&VersionMap> {
+        self.header.as_ref()
+    }
+}
+
+impl<R: Read, T: DeserializeOwned, F: Format> Iterator for VersionedLogReader<R, T, F> {
+    type Item = Result<T, RecordError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.done {
+            let index = self.index;
+            match F::read_record(&mut self.reader, &mut self.buf) {
+                Ok(true) => {}
+                Ok(false) => {
+                    self.done = true;
+                    return None;
+                }
+                Err(e) => {
+                    self.done = true;
+                    return Some(Err(RecordError { index, error: e.into() }));
+                }
+            }
+
+            match with_reused_state(&mut self.state, || F::from_bytes::<LogEntry<T>>(&self.buf)) {
+                Ok(LogEntry::Header(header)) => {
+                    let versions = header.iter().map(|(name, version)| (name.clone(), *version)).collect();
+                    self.state = DeserializationState::new(Default::default(), versions);
+                    self.header = Some(header);
+                }
+                Ok(LogEntry::Record(value)) => {
+                    self.index += 1;
+                    if self.header.is_none() {
+                        return Some(Err(RecordError { index, error: FormatError::new("Record before the first header of the log") }));
+                    }
+                    return Some(Ok(value));
+                }
+                Err(error) => {
+                    self.index += 1;
+                    return Some(Err(RecordError { index, error }));
+                }
+            }
+        }
+        None
+    }
+}
@@ -0,0 +1,63 @@
+use serde::{Deserializer, de::{self, Visitor, SeqAccess, MapAccess, IgnoredAny}};
+
+use crate::{HeaderSeed, VersionMap, VersionedField};
+
+struct PeekVisitor;
+
+impl<'de> Visitor<'de> for PeekVisitor {
+    type Value = VersionMap;
+
+    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        formatter.write_str("{ versions: HashMap<String, u32>, value: T }")
+    }
+
+    fn visit_seq<V>(self, mut seq: V) -> Result<Self::Value, V::Error>
+    where
+        V: SeqAccess<'de>,
+    {
+        let versions = seq.next_element_seed(HeaderSeed)?
+            .ok_or_else(|| de::Error::invalid_length(0, &self))?;
+        // Self-describing formats which serialize structs as arrays, like MessagePack, need the whole sequence to be consumed.
+        // Non-self-describing formats like bincode cannot skip over the value, but they do not require it to be consumed either.
+        // Since the two cannot be told apart here, the error from skipping the value is ignored, so the value is not validated.
+        let _ = seq.next_element::<IgnoredAny>();
+        Ok(versions.into_iter().collect())
+    }
+
+    fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+    where
+        A: MapAccess<'de>,
+    {
+        let mut versions = None;
+        while let Some(key) = map.next_key()? {
+            match key {
+                VersionedField::Versions => {
+                    if versions.is_some() {
+                        return Err(de::Error::duplicate_field("versions"));
+                    }
+                    versions = Some(map.next_value_seed(HeaderSeed)?);
+                }
+                VersionedField::Version => {
+                    return Err(de::Error::custom("Cannot peek the versions of a legacy envelope, since it does not contain any type names"));
+                }
+                VersionedField::Value => {
+                    // Self-describing formats need the whole map to be consumed
+                    map.next_value::<IgnoredAny>()?;
+                }
+            }
+        }
+        versions.map(|versions| versions.into_iter().collect()).ok_or_else(|| de::Error::missing_field("versions"))
+    }
+}
+
+/// Reads the version header of data serialized with [`crate::Versioned`], without deserializing the value.
+///
+/// This is useful for tooling and routing, which need to know which types and versions some data contains without knowing its type.
+///
+/// The value is not validated. In formats which serialize structs as sequences, like bincode or MessagePack, an invalid or truncated value after a valid header is not an error.
+pub fn peek_versions<'de, D>(deserializer: D) -> Result<VersionMap, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    deserializer.deserialize_struct("Versioned", &["versions", "value"], PeekVisitor)
+}
@@ -0,0 +1,114 @@
+use std::{any::TypeId, collections::{BTreeMap, HashMap}, io::Write};
+
+use serde::{Serialize, de::DeserializeOwned};
+#[cfg(feature = "json")]
+use serde::{Deserialize, de::value::MapDeserializer};
+
+use crate::{Format, FormatError, UpgradeReport, upgrade::upgrade_bytes};
+
+type UpgradeFn = fn(&[u8]) -> Result<(UpgradeReport, Vec<u8>), FormatError>;
+
+#[cfg(feature = "json")]
+type MigrateJsonFn = fn(serde_json::Value) -> Result<serde_json::Value, FormatError>;
+
+struct RegisteredType {
+    /// [`upgrade_bytes`] for the type in every registered format, by the [`TypeId`] of the format.
+    upgrade: HashMap<TypeId, UpgradeFn>,
+    #[cfg(feature = "json")]
+    migrate_json: MigrateJsonFn,
+}
+
+#[cfg(feature = "json")]
+fn migrate_json_erased<T: DeserializeOwned + Serialize>(value: serde_json::Value) -> Result<serde_json::Value, FormatError> {
+    // The keys of a json object are sorted, so the value comes before the header.
+    // Reading the header first avoids buffering the value, which deserializing the object as a Versioned<T> would do.
+    let serde_json::Value::Object(envelope) = value else {
+        return Err(FormatError::new("Expected an object with the fields 'versions' and 'value'"));
+    };
+    let (value, header): (Vec<_>, Vec<_>) = envelope.into_iter().partition(|(key, _)| key == "value");
+    let envelope = MapDeserializer::<_, serde_json::Error>::new(header.into_iter().chain(value));
+    let value = crate::Versioned::<T>::deserialize(envelope).map_err(FormatError::new)?.0;
+    serde_json::to_value(crate::Versioned(&value)).map_err(FormatError::new)
+}
+
+/// Migrates data of types that are only known at runtime, by their type key.
+///
+/// The type key is the name of the type in the version header, i.e. [`std::any::type_name`].
+/// Every type must be registered with [`MigrationRegistry::register`] first.
+#[derive(Default)]
+pub struct MigrationRegistry {
+    types: BTreeMap<&'static str, RegisteredType>,
+}
+
+impl MigrationRegistry {
+    pub fn new() -> Self {
+        MigrationRegistry::default()
+    }
+
+    /// Registers a type, so that data stored as `Versioned<T>` can be migrated by its type key.
+    ///
+    /// The formats of this crate enabled by features are registered for the type. Use [`MigrationRegistry::register_format`] for other formats.
+    pub fn register<T: DeserializeOwned + Serialize>(&mut self) -> &mut Self {
+        self.types.insert(std::any::type_name::<T>(), RegisteredType {
+            upgrade: HashMap::new(),
+            #[cfg(feature = "json")]
+            migrate_json: migrate_json_erased::<T>,
+        });
+        #[cfg(feature = "json")]
+        self.register_format::<T, crate::Json>();
+        #[cfg(feature = "bincode")]
+        self.register_format::<T, crate::Bincode>();
+        #[cfg(feature = "postcard")]
+        self.register_format::<T, crate::Postcard>();
+        self
+    }
+
+    /// Registers a format for a type, so that data of the type can be upgraded in that format. The type is registered if it is not yet.
+    pub fn register_format<T: DeserializeOwned + Serialize, F: Format + 'static>(&mut self) -> &mut Self {
+        match self.types.get_mut(std::any::type_name::<T>()) {
+            Some(registered) => {
+                registered.upgrade.insert(TypeId::of::<F>(), upgrade_bytes::<T, F>);
+                self
+            }
+            None => self.register::<T>().register_format::<T, F>(),
+        }
+    }
+
+    pub fn contains(&self, type_key: &str) -> bool {
+        self.types.contains_key(type_key)
+    }
+
+    /// The keys of all registered types, in sorted order.
+    pub fn type_keys(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.types.keys().copied()
+    }
+
+    fn get(&self, type_key: &str) -> Result<&RegisteredType, FormatError> {
+        self.types.get(type_key).ok_or_else(|| FormatError::new(format!("The type {} is not registered", type_key)))
+    }
+
+    fn get_upgrade<F: Format + 'static>(&self, type_key: &str) -> Result<UpgradeFn, FormatError> {
+        self.get(type_key)?.upgrade.get(&TypeId::of::<F>()).copied()
+            .ok_or_else(|| FormatError::new(format!("The format {} is not registered for the type {}", F::NAME, type_key)))
+    }
+
+    /// Like [`crate::upgrade`], for the type with the given key.
+    pub fn upgrade<F: Format + 'static>(&self, type_key: &str, input: &[u8], mut output: impl Write) -> Result<UpgradeReport, FormatError> {
+        let (report, bytes) = self.get_upgrade::<F>(type_key)?(input)?;
+        output.write_all(&bytes)?;
+        Ok(report)
+    }
+
+    /// Like [`crate::upgrade_dry_run`], for the type with the given key.
+    pub fn upgrade_dry_run<F: Format + 'static>(&self, type_key: &str, input: &[u8]) -> Result<UpgradeReport, FormatError> {
+        Ok(self.get_upgrade::<F>(type_key)?(input)?.0)
+    }
+
+    /// Migrates a json value of a `Versioned<T>` to the latest version, for the type with the given key.
+    ///
+    /// Both the input and the output include the version header.
+    #[cfg(feature = "json")]
+    pub fn migrate_json(&self, type_key: &str, value: serde_json::Value) -> Result<serde_json::Value, FormatError> {
+        (self.get(type_key)?.migrate_json)(value)
+    }
+}
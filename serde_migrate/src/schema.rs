@@ -0,0 +1,36 @@
+use std::borrow::Cow;
+
+use schemars::{JsonSchema, Schema, SchemaGenerator, json_schema};
+
+use crate::Versioned;
+
+/// The schema of the envelope, with the version header in `versions` and the latest version of the value in `value`.
+impl<T: JsonSchema> JsonSchema for Versioned<T> {
+    fn schema_name() -> Cow<'static, str> {
+        format!("Versioned_{}", T::schema_name()).into()
+    }
+
+    fn schema_id() -> Cow<'static, str> {
+        format!("serde_migrate::Versioned<{}>", T::schema_id()).into()
+    }
+
+    fn json_schema(generator: &mut SchemaGenerator) -> Schema {
+        let value = generator.subschema_for::<T>();
+        json_schema!({
+            "type": "object",
+            "properties": {
+                "versions": {
+                    "description": "The version of every versioned type in the value, keyed by type name",
+                    "type": "object",
+                    "additionalProperties": {
+                        "type": "integer",
+                        "format": "uint32",
+                        "minimum": 1,
+                    },
+                },
+                "value": value,
+            },
+            "required": ["versions", "value"],
+        })
+    }
+}
@@ -0,0 +1,41 @@
+use std::collections::BTreeMap;
+
+use serde::{Serialize, Serializer, Deserialize, Deserializer};
+
+use crate::{VersionSerializer, with_versions};
+
+/// The versions of all versioned types in a value, keyed by type name.
+pub type VersionMap = BTreeMap<String, u32>;
+
+/// Collects the versions of all versioned types in a value, without serializing it.
+pub fn versions_of<T: Serialize + ?Sized>(value: &T) -> VersionMap {
+    let mut vs = VersionSerializer::default();
+    value.serialize(&mut vs).unwrap();
+    vs.versions.into_iter().map(|(name, version)| (name.to_owned(), version)).collect()
+}
+
+/// Serializes a value without a version header, and returns the version header separately.
+///
+/// This is useful when the version header is stored out-of-band, e.g. in a separate database column or in the header of a wire protocol.
+/// Use [`deserialize_with_versions`] to read the value back.
+pub fn serialize_with_versions<T, S>(value: &T, serializer: S) -> Result<(VersionMap, S::Ok), S::Error>
+where
+    T: Serialize + ?Sized,
+    S: Serializer,
+{
+    let versions = versions_of(value);
+    let payload = value.serialize(serializer)?;
+    Ok((versions, payload))
+}
+
+/// Deserializes a value without a version header, using a version header that was stored out-of-band.
+///
+/// See [`serialize_with_versions`].
+pub fn deserialize_with_versions<'de, T, D>(versions: &VersionMap, deserializer: D) -> Result<T, D::Error>
+where
+    T: Deserialize<'de>,
+    D: Deserializer<'de>,
+{
+    let versions = versions.iter().map(|(name, version)| (name.clone(), *version)).collect();
+    with_versions(versions, || T::deserialize(deserializer))
+}
@@ -0,0 +1,92 @@
+use rusqlite::{
+    Connection, ToSql,
+    types::{FromSql, FromSqlError, FromSqlResult, ToSqlOutput, Value, ValueRef},
+};
+use serde::{Serialize, de::DeserializeOwned};
+
+use crate::{Format, TableReport, Versioned, upgrade::migrate_stored_bytes};
+
+/// Stored as json text. Both text and blob columns containing json can be read.
+impl<T: Serialize> ToSql for Versioned<T> {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        let json = serde_json::to_string(self).map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+        Ok(ToSqlOutput::from(json))
+    }
+}
+
+impl<T: DeserializeOwned> FromSql for Versioned<T> {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        match value {
+            ValueRef::Text(bytes) | ValueRef::Blob(bytes) => serde_json::from_slice(bytes).map_err(|e| FromSqlError::Other(Box::new(e))),
+            _ => Err(FromSqlError::InvalidType),
+        }
+    }
+}
+
+/// Quotes an identifier, so that table and column names can be used in statements.
+fn quote(identifier: &str) -> String {
+    format!("\"{}\"", identifier.replace('"', "\"\""))
+}
+
+/// The number of rows read at a time by [`upgrade_sqlite_column`].
+const BATCH_SIZE: usize = 1000;
+
+/// Upgrades every value in a column to the latest version, in a single transaction.
+///
+/// Only rows whose version header differs from the latest versions are written. Values stored as text are written back as text, and blobs as blobs.
+/// `NULL` values are skipped. Values that cannot be deserialized are reported by `rowid` in [`TableReport::errors`], and are left unchanged.
+/// The table must have a `rowid`, i.e. it must not be a `WITHOUT ROWID` table.
+pub fn upgrade_sqlite_column<T, F>(conn: &mut Connection, table: &str, column: &str) -> rusqlite::Result<TableReport<i64>>
+where
+    T: Serialize + DeserializeOwned,
+    F: Format,
+{
+    let mut report = TableReport::default();
+    let txn = conn.transaction()?;
+    {
+        let mut select = txn.prepare(&format!(
+            "SELECT rowid, {} FROM {} WHERE {} IS NOT NULL AND rowid > ?1 ORDER BY rowid LIMIT {}",
+            quote(column), quote(table), quote(column), BATCH_SIZE,
+        ))?;
+        let mut update = txn.prepare(&format!("UPDATE {} SET {} = ?1 WHERE rowid = ?2", quote(table), quote(column)))?;
+        // Rows are read in batches by rowid, and a batch is updated after it has been read, since the select may see the updated rows otherwise
+        let mut last_rowid = i64::MIN;
+        loop {
+            let mut upgrades = vec![];
+            let mut read = 0;
+            let mut rows = select.query((last_rowid,))?;
+            while let Some(row) = rows.next()? {
+                let rowid: i64 = row.get(0)?;
+                let (stored, is_text) = match row.get_ref(1)? {
+                    ValueRef::Text(bytes) => (bytes, true),
+                    ValueRef::Blob(bytes) => (bytes, false),
+                    other => return Err(rusqlite::Error::InvalidColumnType(1, column.to_owned(), other.data_type())),
+                };
+                read += 1;
+                last_rowid = rowid;
+                match migrate_stored_bytes::<T, F>(stored) {
+                    Ok((_, Some(upgraded))) => {
+                        let upgraded = match String::from_utf8(upgraded) {
+                            Ok(text) if is_text => Value::Text(text),
+                            Ok(text) => Value::Blob(text.into_bytes()),
+                            Err(e) => Value::Blob(e.into_bytes()),
+                        };
+                        upgrades.push((rowid, upgraded));
+                    }
+                    Ok((_, None)) => {}
+                    Err(e) => report.errors.push((rowid, e)),
+                }
+            }
+            for (rowid, upgraded) in upgrades {
+                update.execute((upgraded, rowid))?;
+                report.upgraded += 1;
+            }
+            report.records += read;
+            if read < BATCH_SIZE {
+                break;
+            }
+        }
+    }
+    txn.commit()?;
+    Ok(report)
+}
@@ -0,0 +1,181 @@
+use std::{fmt::Display, marker::PhantomData, ops::Bound};
+
+use redb::{Database, ReadableTable, TableDefinition, TableError};
+use serde::{Serialize, de::DeserializeOwned};
+
+use crate::{Format, FormatError, TableReport, Versioned, upgrade::migrate_stored_bytes};
+
+/// An error from a [`VersionedTable`].
+#[derive(Debug)]
+pub enum StoreError {
+    /// Boxed, since redb errors are large.
+    Database(Box<redb::Error>),
+    /// A value could not be serialized or deserialized.
+    Format(FormatError),
+}
+
+impl Display for StoreError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StoreError::Database(e) => write!(f, "{}", e),
+            StoreError::Format(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for StoreError {}
+
+impl From<FormatError> for StoreError {
+    fn from(e: FormatError) -> Self {
+        StoreError::Format(e)
+    }
+}
+
+impl From<redb::Error> for StoreError {
+    fn from(e: redb::Error) -> Self {
+        StoreError::Database(Box::new(e))
+    }
+}
+
+impl From<redb::TransactionError> for StoreError {
+    fn from(e: redb::TransactionError) -> Self {
+        StoreError::Database(Box::new(e.into()))
+    }
+}
+
+impl From<redb::TableError> for StoreError {
+    fn from(e: redb::TableError) -> Self {
+        StoreError::Database(Box::new(e.into()))
+    }
+}
+
+impl From<redb::StorageError> for StoreError {
+    fn from(e: redb::StorageError) -> Self {
+        StoreError::Database(Box::new(e.into()))
+    }
+}
+
+impl From<redb::CommitError> for StoreError {
+    fn from(e: redb::CommitError) -> Self {
+        StoreError::Database(Box::new(e.into()))
+    }
+}
+
+/// A table of values serialized with [`Versioned`] in a [`redb`] database, with byte string keys.
+///
+/// Values are migrated when they are read. If any migration ran, the upgraded value is written back, so that the migrations only run once per value.
+/// Use [`VersionedTable::upgrade_all`] to upgrade the values that are never read.
+pub struct VersionedTable<'db, T, F> {
+    db: &'db Database,
+    definition: TableDefinition<'db, &'static [u8], &'static [u8]>,
+    _p: PhantomData<(T, F)>,
+}
+
+impl<'db, T, F> VersionedTable<'db, T, F>
+where
+    T: Serialize + DeserializeOwned,
+    F: Format,
+{
+    /// Wraps the table with the given name. The table is created when the first value is written.
+    pub fn new(db: &'db Database, name: &'db str) -> Self {
+        VersionedTable {
+            db,
+            definition: TableDefinition::new(name),
+            _p: PhantomData,
+        }
+    }
+
+    /// Writes a value at the latest version.
+    pub fn insert(&self, key: impl AsRef<[u8]>, value: &T) -> Result<(), StoreError> {
+        let bytes = F::to_bytes(&Versioned(value))?;
+        let txn = self.db.begin_write()?;
+        txn.open_table(self.definition)?.insert(key.as_ref(), bytes.as_slice())?;
+        txn.commit()?;
+        Ok(())
+    }
+
+    /// Removes a value, if it exists.
+    pub fn remove(&self, key: impl AsRef<[u8]>) -> Result<(), StoreError> {
+        let txn = self.db.begin_write()?;
+        txn.open_table(self.definition)?.remove(key.as_ref())?;
+        txn.commit()?;
+        Ok(())
+    }
+
+    /// Reads a value, and runs all migrations on it.
+    ///
+    /// If any migration ran, the upgraded value is written back in a separate transaction.
+    /// The value is only written back if it was not changed by someone else in the meantime.
+    pub fn get(&self, key: impl AsRef<[u8]>) -> Result<Option<T>, StoreError> {
+        let key = key.as_ref();
+        let stored = {
+            let txn = self.db.begin_read()?;
+            let table = match txn.open_table(self.definition) {
+                Ok(table) => table,
+                Err(TableError::TableDoesNotExist(_)) => return Ok(None),
+                Err(e) => return Err(e.into()),
+            };
+            match table.get(key)? {
+                Some(stored) => stored.value().to_vec(),
+                None => return Ok(None),
+            }
+        };
+
+        let (value, upgraded) = migrate_stored_bytes::<T, F>(&stored)?;
+        let Some(upgraded) = upgraded else {
+            return Ok(Some(value));
+        };
+        let txn = self.db.begin_write()?;
+        {
+            let mut table = txn.open_table(self.definition)?;
+            let unchanged = table.get(key)?.is_some_and(|current| current.value() == stored.as_slice());
+            if unchanged {
+                table.insert(key, upgraded.as_slice())?;
+            }
+        }
+        txn.commit()?;
+        Ok(Some(value))
+    }
+
+    /// Upgrades every value in the table to the latest version, `batch_size` values at a time.
+    ///
+    /// Every batch is a separate write transaction, so the table can be used while it is being upgraded, e.g. from a background thread.
+    /// Values that cannot be deserialized are reported in [`TableReport::errors`], and are left unchanged.
+    pub fn upgrade_all(&self, batch_size: usize) -> Result<TableReport, StoreError> {
+        let batch_size = batch_size.max(1);
+        let mut report = TableReport::default();
+        let mut last_key: Option<Vec<u8>> = None;
+        loop {
+            let txn = self.db.begin_write()?;
+            let done = {
+                let mut table = txn.open_table(self.definition)?;
+                let start = match &last_key {
+                    Some(key) => Bound::Excluded(key.as_slice()),
+                    None => Bound::Unbounded,
+                };
+                let mut upgrades = vec![];
+                let mut read = 0;
+                for entry in table.range::<&[u8]>((start, Bound::Unbounded))?.take(batch_size) {
+                    let (key, stored) = entry?;
+                    read += 1;
+                    match migrate_stored_bytes::<T, F>(stored.value()) {
+                        Ok((_, Some(upgraded))) => upgrades.push((key.value().to_vec(), upgraded)),
+                        Ok((_, None)) => {}
+                        Err(e) => report.errors.push((key.value().to_vec(), e)),
+                    }
+                    last_key = Some(key.value().to_vec());
+                }
+                for (key, upgraded) in &upgrades {
+                    table.insert(key.as_slice(), upgraded.as_slice())?;
+                }
+                report.records += read;
+                report.upgraded += upgrades.len();
+                read < batch_size
+            };
+            txn.commit()?;
+            if done {
+                return Ok(report);
+            }
+        }
+    }
+}
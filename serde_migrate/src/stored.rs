@@ -0,0 +1,68 @@
+use serde::{Deserialize, Deserializer};
+
+/// Implemented by the #[macro@crate::versioned] macro.
+///
+/// Converts the stored representation of a type (an enum with one variant per version) to the latest version.
+#[doc(hidden)]
+pub trait MigrateStored: Sized {
+    type Stored;
+
+    fn migrate_stored(stored: Self::Stored) -> Self;
+}
+
+/// Implemented by the #[macro@crate::versioned] macro.
+///
+/// Deserializes a type at the version it was stored with, without running any migrations.
+#[doc(hidden)]
+pub trait DeserializeStored<'de>: MigrateStored {
+    fn deserialize_stored<D>(deserializer: D) -> Result<(u32, Self::Stored), D::Error>
+    where
+        D: Deserializer<'de>;
+}
+
+/// A versioned value, deserialized at the version it was stored with.
+///
+/// Normally, nested versioned types are migrated to their latest version before the migrations of the outer type run.
+/// If the outer type needs to see the old representation, e.g. because a field is moved from the inner type to the outer type,
+/// the field can be declared as `Stored<Inner>` in the older versions of the outer type.
+/// The serialized data is the same, so this does not change the data format.
+///
+/// The stored representation is the `DataVersions` enum of the generated migrations module, with one variant per version.
+/// Call [`Stored::migrate`] to run the remaining migrations of the inner type.
+pub struct Stored<T: MigrateStored> {
+    version: u32,
+    value: T::Stored,
+}
+
+impl<T: MigrateStored> Stored<T> {
+    /// The version the value was stored with.
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+
+    pub fn get(&self) -> &T::Stored {
+        &self.value
+    }
+
+    pub fn into_inner(self) -> T::Stored {
+        self.value
+    }
+
+    /// Runs all migrations, and returns the latest version of the value.
+    pub fn migrate(self) -> T {
+        T::migrate_stored(self.value)
+    }
+}
+
+impl<'de, T: DeserializeStored<'de>> Deserialize<'de> for Stored<T> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let (version, value) = T::deserialize_stored(deserializer)?;
+        Ok(Stored {
+            version,
+            value,
+        })
+    }
+}
@@ -0,0 +1,131 @@
+use std::{fmt::Display, io::{BufReader, Read, Write}, marker::PhantomData};
+
+use serde::{Serialize, de::DeserializeOwned};
+
+use crate::{Format, FormatError, Versioned, upgrade::upgrade_bytes};
+
+/// An error for a single record in a stream of records.
+#[derive(Debug)]
+pub struct RecordError {
+    /// The index of the record in the stream, starting at 0.
+    pub index: usize,
+    pub error: FormatError,
+}
+
+impl Display for RecordError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "record {}: {}", self.index, self.error)
+    }
+}
+
+impl std::error::Error for RecordError {}
+
+/// Reads a stream of records serialized with [`Versioned`] one at a time, and runs all migrations on each of them.
+///
+/// Records are framed as described by [`Format::read_record`]: newline-delimited for json, and length-prefixed for binary formats.
+/// Only one record is kept in memory at a time.
+///
+/// If a record cannot be deserialized, an error is returned for that record, and reading continues with the next one.
+/// If the stream itself cannot be read, e.g. because a record is truncated, an error is returned and iteration ends.
+pub struct RecordReader<R, T, F> {
+    reader: BufReader<R>,
+    buf: Vec<u8>,
+    index: usize,
+    done: bool,
+    _p: PhantomData<(T, F)>,
+}
+
+impl<R: Read, T: DeserializeOwned, F: Format> RecordReader<R, T, F> {
+    pub fn new(reader: R) -> Self {
+        RecordReader {
+            reader: BufReader::new(reader),
+            buf: Vec::new(),
+            index: 0,
+            done: false,
+            _p: PhantomData,
+        }
+    }
+
+    /// Reads the next raw record. The record is available in `self.buf`.
+    fn next_raw(&mut self) -> Option<Result<(), RecordError>> {
+        if self.done {
+            return None;
+        }
+        match F::read_record(&mut self.reader, &mut self.buf) {
+            Ok(true) => Some(Ok(())),
+            Ok(false) => {
+                self.done = true;
+                None
+            }
+            Err(e) => {
+                self.done = true;
+                Some(Err(RecordError {
+                    index: self.index,
+                    error: e.into(),
+                }))
+            }
+        }
+    }
+}
+
+impl<R: Read, T: DeserializeOwned, F: Format> Iterator for RecordReader<R, T, F> {
+    type Item = Result<T, RecordError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let raw = self.next_raw()?;
+        let index = self.index;
+        self.index += 1;
+        Some(raw.and_then(|_| {
+            F::from_bytes::<Versioned<T>>(&self.buf)
+                .map(|v| v.0)
+                .map_err(|error| RecordError { index, error })
+        }))
+    }
+}
+
+/// The result of upgrading a stream of records with [`upgrade_records`].
+#[derive(Debug, Default)]
+pub struct StreamReport {
+    /// The number of records that were read.
+    pub records: usize,
+    /// The number of records whose version header changed when upgrading them. See [`crate::UpgradeReport::changed`].
+    pub changed: usize,
+    /// Records that could not be upgraded. These are written to the output unchanged.
+    pub errors: Vec<RecordError>,
+}
+
+/// Reads a stream of records serialized with [`Versioned`], runs all migrations on each of them, and writes them to `writer` at the latest version.
+///
+/// Records that cannot be deserialized are reported in [`StreamReport::errors`], and are copied to the output unchanged, so that no data is lost.
+/// Only one record is kept in memory at a time. An error is only returned if the output cannot be written.
+pub fn upgrade_records<T, F>(reader: impl Read, mut writer: impl Write) -> Result<StreamReport, FormatError>
+where
+    T: DeserializeOwned + Serialize,
+    F: Format,
+{
+    let mut records = RecordReader::<_, T, F>::new(reader);
+    let mut report = StreamReport::default();
+    while let Some(raw) = records.next_raw() {
+        let index = records.index;
+        records.index += 1;
+        if let Err(e) = raw {
+            report.errors.push(e);
+            break;
+        }
+        report.records += 1;
+
+        match upgrade_bytes::<T, F>(&records.buf) {
+            Ok((upgrade_report, bytes)) => {
+                if upgrade_report.changed {
+                    report.changed += 1;
+                }
+                F::write_record(&mut writer, &bytes)?;
+            }
+            Err(error) => {
+                report.errors.push(RecordError { index, error });
+                F::write_record(&mut writer, &records.buf)?;
+            }
+        }
+    }
+    Ok(report)
+}
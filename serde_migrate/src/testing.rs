@@ -0,0 +1,130 @@
+//! Helpers for testing migration chains against stored fixtures.
+//!
+//! A fixture directory contains data stored with every version of a type, in every enabled format, as `v1.json`, `v1.bincode`, `v1.postcard` and so on.
+//! Next to them, `v1.expected.json` contains the value that the data of version 1 should be migrated to, serialized at the latest version.
+//!
+//! Fixtures for old versions cannot be created after the fact, since the old structs no longer exist.
+//! Instead, run the tests with `SERDE_MIGRATE_BLESS=1` whenever a version is added, to store fixtures for it, and commit them.
+
+use std::{fs, path::Path};
+
+use serde::{Serialize, de::DeserializeOwned};
+
+use crate::{Format, Versioned, VersionedType};
+
+/// The environment variable which makes [`assert_migrates`] write missing fixtures and update expected values.
+pub const BLESS_ENV: &str = "SERDE_MIGRATE_BLESS";
+
+/// Checks that the fixtures in `dir` for every version of `T` still deserialize, and migrate to the expected values.
+///
+/// `sample` is only used in bless mode, to write the fixtures for the current version if they do not exist yet.
+/// Versions without any fixtures are skipped, except for the current version.
+///
+/// # Panics
+///
+/// Panics with a list of all problems if any fixture fails.
+pub fn assert_migrates<T>(dir: impl AsRef<Path>, sample: &T)
+where
+    T: VersionedType + Serialize + DeserializeOwned,
+{
+    let bless = std::env::var_os(BLESS_ENV).is_some_and(|v| !v.is_empty() && v != "0");
+    let dir = dir.as_ref();
+    if let Err(problems) = check_migrations(dir, sample, bless) {
+        panic!(
+            "Migration fixtures for {} in {} failed:\n  {}\nIf the changes are intended, run with {}=1 to update the fixtures",
+            T::type_key(), dir.display(), problems.join("\n  "), BLESS_ENV,
+        );
+    }
+}
+
+/// Like [`assert_migrates`], but returns the problems instead of panicking, and takes the bless mode as an argument.
+pub fn check_migrations<T>(dir: &Path, sample: &T, bless: bool) -> Result<(), Vec<String>>
+where
+    T: VersionedType + Serialize + DeserializeOwned,
+{
+    let mut problems = vec![];
+    if bless {
+        if let Err(e) = fs::create_dir_all(dir) {
+            return Err(vec![format!("{}: {}", dir.display(), e)]);
+        }
+    }
+
+    for info in T::history() {
+        let version = info.version;
+        let mut results = vec![];
+        check_fixture::<T, crate::Json>(dir, version, sample, bless, &mut results, &mut problems);
+        #[cfg(feature = "bincode")]
+        check_fixture::<T, crate::Bincode>(dir, version, sample, bless, &mut results, &mut problems);
+        #[cfg(feature = "postcard")]
+        check_fixture::<T, crate::Postcard>(dir, version, sample, bless, &mut results, &mut problems);
+
+        let Some((first_format, actual, pretty)) = results.first() else {
+            continue;
+        };
+        for (format, other, _) in &results[1..] {
+            if other != actual {
+                problems.push(format!("v{}.{} migrates to a different value than v{}.{}", version, format, version, first_format));
+            }
+        }
+
+        let expected_path = dir.join(format!("v{}.expected.json", version));
+        let expected = fs::read(&expected_path).map_err(|e| e.to_string())
+            .and_then(|bytes| serde_json::from_slice::<serde_json::Value>(&bytes).map_err(|e| e.to_string()));
+        match expected {
+            Ok(expected) if &expected == actual => {}
+            _ if bless => {
+                if let Err(e) = fs::write(&expected_path, pretty) {
+                    problems.push(format!("{}: {}", expected_path.display(), e));
+                }
+            }
+            Ok(_) => problems.push(format!("v{} migrates to {}, which differs from v{}.expected.json", version, actual, version)),
+            Err(e) => problems.push(format!("{}: {}", expected_path.display(), e)),
+        }
+    }
+
+    if problems.is_empty() {
+        Ok(())
+    } else {
+        Err(problems)
+    }
+}
+
+/// Reads the fixture of a version in a format, and adds the migrated value to `results`, both as a json value and as pretty-printed json.
+fn check_fixture<T, F>(dir: &Path, version: u32, sample: &T, bless: bool, results: &mut Vec<(&'static str, serde_json::Value, String)>, problems: &mut Vec<String>)
+where
+    T: VersionedType + Serialize + DeserializeOwned,
+    F: Format,
+{
+    let path = dir.join(format!("v{}.{}", version, F::NAME));
+    let bytes = match fs::read(&path) {
+        Ok(bytes) => bytes,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound && version == T::CURRENT_VERSION => {
+            if !bless {
+                problems.push(format!("{}: Missing fixture for the current version", path.display()));
+                return;
+            }
+            let bytes = match F::to_bytes(&Versioned(sample)) {
+                Ok(bytes) => bytes,
+                Err(e) => return problems.push(format!("{}: {}", path.display(), e)),
+            };
+            if let Err(e) = fs::write(&path, &bytes) {
+                return problems.push(format!("{}: {}", path.display(), e));
+            }
+            bytes
+        }
+        // Fixtures of old versions cannot be created anymore
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return,
+        Err(e) => return problems.push(format!("{}: {}", path.display(), e)),
+    };
+
+    // The keys of a json value are sorted, so the pretty-printed json is serialized separately to keep the header first
+    let migrated = F::from_bytes::<Versioned<T>>(&bytes).and_then(|value| {
+        let json = serde_json::to_value(Versioned(&value.0)).map_err(crate::FormatError::new)?;
+        let pretty = serde_json::to_string_pretty(&Versioned(&value.0)).map_err(crate::FormatError::new)?;
+        Ok((json, pretty + "\n"))
+    });
+    match migrated {
+        Ok((json, pretty)) => results.push((F::NAME, json, pretty)),
+        Err(e) => problems.push(format!("{}: {}", path.display(), e)),
+    }
+}
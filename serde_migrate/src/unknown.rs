@@ -0,0 +1,401 @@
+use std::marker::PhantomData;
+
+use serde::{Serialize, Serializer, Deserialize, Deserializer, de::{self, DeserializeSeed, Visitor, SeqAccess, MapAccess, IntoDeserializer, value::{SeqDeserializer, MapDeserializer, MapAccessDeserializer}}, ser::{SerializeMap, SerializeSeq}};
+
+/// A format-independent representation of a value.
+///
+/// It is used for fields that were not recognized when deserializing a struct marked with `#[versioned(preserve_unknown)]`,
+/// and for documents passed to [`crate::Schema`] migrations.
+/// Since the value is captured without knowing its type, it can only be captured by self-describing formats like json.
+#[derive(Clone, Debug, PartialEq)]
+pub enum UnknownValue {
+    Unit,
+    Bool(bool),
+    I64(i64),
+    U64(u64),
+    F64(f64),
+    String(String),
+    Bytes(Vec<u8>),
+    None,
+    Some(Box<UnknownValue>),
+    Seq(Vec<UnknownValue>),
+    Map(Vec<(UnknownValue, UnknownValue)>),
+}
+
+impl UnknownValue {
+    /// Returns the value of a key, if this is a map.
+    pub fn get(&self, key: &str) -> Option<&UnknownValue> {
+        match self {
+            UnknownValue::Map(entries) => entries.iter().find(|(k, _)| matches!(k, UnknownValue::String(k) if k == key)).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    /// Returns the value of a key, if this is a map.
+    pub fn get_mut(&mut self, key: &str) -> Option<&mut UnknownValue> {
+        match self {
+            UnknownValue::Map(entries) => entries.iter_mut().find(|(k, _)| matches!(k, UnknownValue::String(k) if k == key)).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    /// Inserts a value into a map, replacing the previous value of the key.
+    /// Returns the previous value, or `None` if there was no previous value or this is not a map.
+    pub fn insert(&mut self, key: &str, value: UnknownValue) -> Option<UnknownValue> {
+        if let Some(v) = self.get_mut(key) {
+            return Some(std::mem::replace(v, value));
+        }
+        if let UnknownValue::Map(entries) = self {
+            entries.push((UnknownValue::String(key.to_owned()), value));
+        }
+        None
+    }
+
+    /// Removes a key from a map, and returns its value.
+    pub fn remove(&mut self, key: &str) -> Option<UnknownValue> {
+        match self {
+            UnknownValue::Map(entries) => {
+                let i = entries.iter().position(|(k, _)| matches!(k, UnknownValue::String(k) if k == key))?;
+                Some(entries.remove(i).1)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Fields which were not recognized by the latest version of a struct marked with `#[versioned(preserve_unknown)]`.
+///
+/// The fields are written back as-is when the struct is serialized again. If the data was stored by a newer
+/// version of the struct, the stored version is kept as well, so that the data is not downgraded.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct UnknownFields {
+    stored_version: Option<u32>,
+    fields: Vec<(String, UnknownValue)>,
+}
+
+impl UnknownFields {
+    /// The version the data was stored with, if it was deserialized from data at the latest version or newer.
+    pub fn stored_version(&self) -> Option<u32> {
+        self.stored_version
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.fields.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.fields.len()
+    }
+
+    pub fn get(&self, name: &str) -> Option<&UnknownValue> {
+        self.fields.iter().find(|(k, _)| k == name).map(|(_, v)| v)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &UnknownValue)> {
+        self.fields.iter().map(|(k, v)| (k.as_str(), v))
+    }
+
+    #[doc(hidden)]
+    pub fn with_stored_version(mut self, version: u32) -> Self {
+        self.stored_version = Some(version);
+        self
+    }
+}
+
+impl Serialize for UnknownValue {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match self {
+            UnknownValue::Unit => serializer.serialize_unit(),
+            UnknownValue::Bool(v) => serializer.serialize_bool(*v),
+            UnknownValue::I64(v) => serializer.serialize_i64(*v),
+            UnknownValue::U64(v) => serializer.serialize_u64(*v),
+            UnknownValue::F64(v) => serializer.serialize_f64(*v),
+            UnknownValue::String(v) => serializer.serialize_str(v),
+            UnknownValue::Bytes(v) => serializer.serialize_bytes(v),
+            UnknownValue::None => serializer.serialize_none(),
+            UnknownValue::Some(v) => serializer.serialize_some(v),
+            UnknownValue::Seq(v) => {
+                let mut seq = serializer.serialize_seq(Some(v.len()))?;
+                for e in v {
+                    seq.serialize_element(e)?;
+                }
+                seq.end()
+            }
+            UnknownValue::Map(v) => {
+                let mut map = serializer.serialize_map(Some(v.len()))?;
+                for (k, e) in v {
+                    map.serialize_entry(k, e)?;
+                }
+                map.end()
+            }
+        }
+    }
+}
+
+struct UnknownValueVisitor;
+
+impl<'de> Visitor<'de> for UnknownValueVisitor {
+    type Value = UnknownValue;
+
+    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        formatter.write_str("any value")
+    }
+
+    fn visit_bool<E>(self, v: bool) -> Result<Self::Value, E> {
+        Ok(UnknownValue::Bool(v))
+    }
+
+    fn visit_i64<E>(self, v: i64) -> Result<Self::Value, E> {
+        Ok(UnknownValue::I64(v))
+    }
+
+    fn visit_u64<E>(self, v: u64) -> Result<Self::Value, E> {
+        Ok(UnknownValue::U64(v))
+    }
+
+    fn visit_f64<E>(self, v: f64) -> Result<Self::Value, E> {
+        Ok(UnknownValue::F64(v))
+    }
+
+    fn visit_str<E>(self, v: &str) -> Result<Self::Value, E> {
+        Ok(UnknownValue::String(v.to_owned()))
+    }
+
+    fn visit_string<E>(self, v: String) -> Result<Self::Value, E> {
+        Ok(UnknownValue::String(v))
+    }
+
+    fn visit_bytes<E>(self, v: &[u8]) -> Result<Self::Value, E> {
+        Ok(UnknownValue::Bytes(v.to_owned()))
+    }
+
+    fn visit_byte_buf<E>(self, v: Vec<u8>) -> Result<Self::Value, E> {
+        Ok(UnknownValue::Bytes(v))
+    }
+
+    fn visit_unit<E>(self) -> Result<Self::Value, E> {
+        Ok(UnknownValue::Unit)
+    }
+
+    fn visit_none<E>(self) -> Result<Self::Value, E> {
+        Ok(UnknownValue::None)
+    }
+
+    fn visit_some<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        Ok(UnknownValue::Some(Box::new(UnknownValue::deserialize(deserializer)?)))
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+    where
+        A: SeqAccess<'de>,
+    {
+        let mut values = Vec::new();
+        while let Some(v) = seq.next_element()? {
+            values.push(v);
+        }
+        Ok(UnknownValue::Seq(values))
+    }
+
+    fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+    where
+        A: MapAccess<'de>,
+    {
+        let mut entries = Vec::new();
+        while let Some(entry) = map.next_entry()? {
+            entries.push(entry);
+        }
+        Ok(UnknownValue::Map(entries))
+    }
+}
+
+impl<'de> Deserialize<'de> for UnknownValue {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_any(UnknownValueVisitor)
+    }
+}
+
+impl Serialize for UnknownFields {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut map = serializer.serialize_map(Some(self.fields.len()))?;
+        for (k, v) in &self.fields {
+            map.serialize_entry(k, v)?;
+        }
+        map.end()
+    }
+}
+
+struct UnknownFieldsVisitor;
+
+impl<'de> Visitor<'de> for UnknownFieldsVisitor {
+    type Value = UnknownFields;
+
+    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        formatter.write_str("a map of fields")
+    }
+
+    fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+    where
+        A: MapAccess<'de>,
+    {
+        let mut fields = Vec::new();
+        while let Some(entry) = map.next_entry()? {
+            fields.push(entry);
+        }
+        Ok(UnknownFields {
+            stored_version: None,
+            fields,
+        })
+    }
+}
+
+impl<'de> Deserialize<'de> for UnknownFields {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_map(UnknownFieldsVisitor)
+    }
+}
+
+/// Buffers a value, and records whether it was read from a human-readable format, so that it can be deserialized from the buffer the same way.
+pub(crate) struct BufferSeed;
+
+impl<'de> DeserializeSeed<'de> for BufferSeed {
+    type Value = (UnknownValue, bool);
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        let is_human_readable = deserializer.is_human_readable();
+        Ok((UnknownValue::deserialize(deserializer)?, is_human_readable))
+    }
+}
+
+/// The deserializer is human-readable. Use [`UnknownValueDeserializer::new`] for values read from other formats.
+impl<'de, E: de::Error> IntoDeserializer<'de, E> for UnknownValue {
+    type Deserializer = UnknownValueDeserializer<E>;
+
+    fn into_deserializer(self) -> Self::Deserializer {
+        UnknownValueDeserializer::new(self, true)
+    }
+}
+
+/// Deserializes a type from an [`UnknownValue`].
+pub struct UnknownValueDeserializer<E> {
+    value: UnknownValue,
+    is_human_readable: bool,
+    _e: PhantomData<E>,
+}
+
+impl<E> UnknownValueDeserializer<E> {
+    /// Types like `IpAddr` are serialized differently by human-readable formats.
+    /// `is_human_readable` should match the format the value was read from, so that such types are deserialized the same way.
+    pub fn new(value: UnknownValue, is_human_readable: bool) -> Self {
+        UnknownValueDeserializer {
+            value,
+            is_human_readable,
+            _e: PhantomData,
+        }
+    }
+}
+
+impl<'de, E: de::Error> IntoDeserializer<'de, E> for UnknownValueDeserializer<E> {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+impl<'de, E: de::Error> Deserializer<'de> for UnknownValueDeserializer<E> {
+    type Error = E;
+
+    fn is_human_readable(&self) -> bool {
+        self.is_human_readable
+    }
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, E>
+    where
+        V: Visitor<'de>,
+    {
+        let is_human_readable = self.is_human_readable;
+        let nested = move |value| UnknownValueDeserializer::<E>::new(value, is_human_readable);
+        match self.value {
+            UnknownValue::Unit => visitor.visit_unit(),
+            UnknownValue::Bool(v) => visitor.visit_bool(v),
+            UnknownValue::I64(v) => visitor.visit_i64(v),
+            UnknownValue::U64(v) => visitor.visit_u64(v),
+            UnknownValue::F64(v) => visitor.visit_f64(v),
+            UnknownValue::String(v) => visitor.visit_string(v),
+            UnknownValue::Bytes(v) => visitor.visit_byte_buf(v),
+            UnknownValue::None => visitor.visit_none(),
+            UnknownValue::Some(v) => visitor.visit_some(nested(*v)),
+            UnknownValue::Seq(v) => {
+                let mut seq = SeqDeserializer::new(v.into_iter().map(nested));
+                let value = visitor.visit_seq(&mut seq)?;
+                seq.end()?;
+                Ok(value)
+            }
+            UnknownValue::Map(v) => {
+                let mut map = MapDeserializer::new(v.into_iter().map(|(k, v)| (nested(k), nested(v))));
+                let value = visitor.visit_map(&mut map)?;
+                map.end()?;
+                Ok(value)
+            }
+        }
+    }
+
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value, E>
+    where
+        V: Visitor<'de>,
+    {
+        match self.value {
+            UnknownValue::None | UnknownValue::Unit => visitor.visit_none(),
+            UnknownValue::Some(v) => visitor.visit_some(UnknownValueDeserializer::new(*v, self.is_human_readable)),
+            _ => visitor.visit_some(self),
+        }
+    }
+
+    fn deserialize_newtype_struct<V>(self, _name: &'static str, visitor: V) -> Result<V::Value, E>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, E>
+    where
+        V: Visitor<'de>,
+    {
+        let is_human_readable = self.is_human_readable;
+        let nested = move |value| UnknownValueDeserializer::<E>::new(value, is_human_readable);
+        match self.value {
+            UnknownValue::String(v) => visitor.visit_enum(v.into_deserializer()),
+            UnknownValue::Map(v) if v.len() == 1 => {
+                visitor.visit_enum(MapAccessDeserializer::new(MapDeserializer::new(v.into_iter().map(|(k, v)| (nested(k), nested(v))))))
+            }
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct seq tuple
+        tuple_struct map struct identifier ignored_any
+    }
+}
@@ -0,0 +1,123 @@
+use std::io::Write;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer, de::DeserializeOwned, ser};
+
+use crate::{Format, FormatError, Versioned, VersionMap, versions_of};
+
+/// The result of upgrading some data to the latest version.
+#[derive(Debug, Clone, PartialEq)]
+pub struct UpgradeReport {
+    /// Whether the data has to be written again, i.e. whether the stored header differs from the latest versions.
+    pub changed: bool,
+    /// The versions stored in the input. `None` if the header does not contain type names, i.e. for the legacy single-version envelope.
+    pub stored_versions: Option<VersionMap>,
+    /// The versions of the upgraded data.
+    pub latest_versions: VersionMap,
+}
+
+impl UpgradeReport {
+    /// The types whose version changed, as `(type name, stored version, latest version)`.
+    ///
+    /// Types which are missing from the stored header are treated as version 1, like when deserializing.
+    /// Nothing is returned if the stored versions are unknown.
+    pub fn migrations(&self) -> impl Iterator<Item = (&str, u32, u32)> {
+        let stored_versions = self.stored_versions.as_ref();
+        self.latest_versions.iter().filter_map(move |(name, &latest)| {
+            let stored = stored_versions?.get(name).copied().unwrap_or(1);
+            (stored != latest).then_some((name.as_str(), stored, latest))
+        })
+    }
+}
+
+/// The result of upgrading every value in a table, e.g. with [`VersionedTable::upgrade_all`](crate::VersionedTable::upgrade_all).
+#[cfg(any(feature = "redb", feature = "rusqlite"))]
+#[derive(Debug, Default)]
+pub struct TableReport<K = Vec<u8>> {
+    /// The number of values that were read.
+    pub records: usize,
+    /// The number of values that were written back at the latest version.
+    pub upgraded: usize,
+    /// Values that could not be deserialized, by key. These are left unchanged.
+    pub errors: Vec<(K, FormatError)>,
+}
+
+/// Reads versioned data, runs all migrations, and writes it back with the latest version of all types.
+///
+/// This is useful for pre-migrating stored data before raising the `min_supported` version of a type.
+/// [`upgrade`] does the same for the raw bytes of a [`Format`], and also reports which types were migrated.
+pub fn migrate_to_latest<'de, T, D, S>(deserializer: D, serializer: S) -> Result<S::Ok, S::Error>
+where
+    T: Deserialize<'de> + Serialize,
+    D: Deserializer<'de>,
+    S: Serializer,
+{
+    let value = Versioned::<T>::deserialize(deserializer).map_err(ser::Error::custom)?;
+    Versioned(&value.0).serialize(serializer)
+}
+
+/// Migrates the stored bytes of a value. Also returns the upgraded bytes, if the value has to be written again, as decided by [`UpgradeReport::changed`].
+#[cfg(any(feature = "redb", feature = "rusqlite"))]
+pub(crate) fn migrate_stored_bytes<T, F>(stored: &[u8]) -> Result<(T, Option<Vec<u8>>), FormatError>
+where
+    T: DeserializeOwned + Serialize,
+    F: Format,
+{
+    let (value, report) = migrate_bytes::<T, F>(stored)?;
+    let upgraded = if report.changed {
+        Some(F::to_bytes(&Versioned(&value))?)
+    } else {
+        None
+    };
+    Ok((value, upgraded))
+}
+
+/// Reads the bytes of a value and runs all migrations, without writing it back. The value has to be written again if the report is `changed`.
+pub(crate) fn migrate_bytes<T, F>(input: &[u8]) -> Result<(T, UpgradeReport), FormatError>
+where
+    T: DeserializeOwned + Serialize,
+    F: Format,
+{
+    let value = F::from_bytes::<Versioned<T>>(input)?.0;
+    // The value could be read, so the header can only be unreadable if it does not contain type names
+    let stored_versions = F::peek_versions(input).ok();
+    let latest_versions = versions_of(&value);
+    let report = UpgradeReport {
+        changed: stored_versions.as_ref() != Some(&latest_versions),
+        stored_versions,
+        latest_versions,
+    };
+    Ok((value, report))
+}
+
+/// Like [`migrate_to_latest`], but for the bytes of a [`Format`].
+pub(crate) fn upgrade_bytes<T, F>(input: &[u8]) -> Result<(UpgradeReport, Vec<u8>), FormatError>
+where
+    T: DeserializeOwned + Serialize,
+    F: Format,
+{
+    let (value, report) = migrate_bytes::<T, F>(input)?;
+    let output = F::to_bytes(&Versioned(&value))?;
+    Ok((report, output))
+}
+
+/// Reads data serialized with [`Versioned`], runs all migrations, and writes it back at the latest version.
+///
+/// The upgraded data is always written to `output`, even if it did not change.
+pub fn upgrade<T, F>(input: &[u8], mut output: impl Write) -> Result<UpgradeReport, FormatError>
+where
+    T: DeserializeOwned + Serialize,
+    F: Format,
+{
+    let (report, bytes) = upgrade_bytes::<T, F>(input)?;
+    output.write_all(&bytes)?;
+    Ok(report)
+}
+
+/// Like [`upgrade`], but only reports what would be migrated, without writing anything.
+pub fn upgrade_dry_run<T, F>(input: &[u8]) -> Result<UpgradeReport, FormatError>
+where
+    T: DeserializeOwned + Serialize,
+    F: Format,
+{
+    Ok(upgrade_bytes::<T, F>(input)?.0)
+}
@@ -0,0 +1,20 @@
+use serde::{Serialize, de::DeserializeOwned};
+
+use crate::{MigrateStored, VersionMap, deserialize_with_versions};
+
+/// Migrates json values in memory, without a round trip through text.
+///
+/// Implemented for all types with the #[macro@crate::versioned] attribute.
+pub trait MigrateValue {
+    /// Migrates a value stored with the given versions, and returns the latest representation.
+    ///
+    /// Neither the input nor the output contain the version header. Use [`crate::MigrationRegistry::migrate_json`] for values which include it.
+    fn migrate_value(value: serde_json::Value, versions: &VersionMap) -> Result<serde_json::Value, serde_json::Error>;
+}
+
+impl<T: MigrateStored + DeserializeOwned + Serialize> MigrateValue for T {
+    fn migrate_value(value: serde_json::Value, versions: &VersionMap) -> Result<serde_json::Value, serde_json::Error> {
+        let value: T = deserialize_with_versions(versions, value)?;
+        serde_json::to_value(value)
+    }
+}
@@ -0,0 +1,115 @@
+#![cfg(feature = "arbitrary")]
+
+use serde_migrate::{arbitrary::{Arbitrary, Unstructured}, assert_arbitrary_migrates, assert_random_migrations, peek_versions, versioned, ArbitraryVersions, Bincode, Format, Json, Postcard};
+
+#[versioned(arbitrary)]
+#[derive(Debug, PartialEq)]
+struct Inner {
+    pub id: u32,
+    #[version(start = 2)]
+    pub tags: Vec<String>,
+}
+
+impl inner_migrations::Migrate for Inner {
+    fn to_v2(v: inner_migrations::InnerV1) -> inner_migrations::InnerV2 {
+        inner_migrations::InnerV2 { id: v.id, tags: vec![] }
+    }
+}
+
+#[versioned(arbitrary)]
+#[derive(Debug, PartialEq)]
+struct Outer {
+    #[version(end = 2)]
+    pub count: u8,
+    #[version(start = 2)]
+    pub total: u64,
+    #[version(start = 3)]
+    pub ratio: f64,
+    pub inner: Inner,
+}
+
+impl outer_migrations::Migrate for Outer {
+    fn to_v2(v: outer_migrations::OuterV1) -> outer_migrations::OuterV2 {
+        outer_migrations::OuterV2 { total: v.count as u64, inner: v.inner }
+    }
+
+    fn to_v3(v: outer_migrations::OuterV2) -> outer_migrations::OuterV3 {
+        outer_migrations::OuterV3 { total: v.total, ratio: 1.0, inner: v.inner }
+    }
+}
+
+#[versioned(arbitrary)]
+struct Generic<T> {
+    #[version(end = 2)]
+    pub a: T,
+    #[version(start = 2)]
+    pub b: Vec<T>,
+}
+
+impl<T> generic_migrations::Migrate<T> for Generic<T> {
+    fn to_v2(v: generic_migrations::GenericV1<T>) -> generic_migrations::GenericV2<T> {
+        generic_migrations::GenericV2 { b: vec![v.a] }
+    }
+}
+
+#[versioned(arbitrary)]
+struct Panics {
+    #[version(end = 2)]
+    pub a: u32,
+    #[version(start = 2)]
+    pub b: u32,
+}
+
+impl panics_migrations::Migrate for Panics {
+    fn to_v2(_: panics_migrations::PanicsV1) -> panics_migrations::PanicsV2 {
+        panic!("to_v2 is broken");
+    }
+}
+
+#[test]
+fn test_random_migrations() {
+    assert!(assert_random_migrations::<Outer, Json>(200) > 0);
+    assert!(assert_random_migrations::<Outer, Bincode>(200) > 0);
+    assert!(assert_random_migrations::<Outer, Postcard>(200) > 0);
+    assert!(assert_random_migrations::<Generic<String>, Json>(200) > 0);
+}
+
+#[test]
+fn test_arbitrary_latest_version() {
+    let data = [7u8; 64];
+    let value = Outer::arbitrary(&mut Unstructured::new(&data)).unwrap();
+    assert_eq!(value.inner.id, u32::from_le_bytes([7; 4]));
+}
+
+#[test]
+fn test_version_bytes_have_header() {
+    let data = [1u8; 64];
+    let bytes = Outer::arbitrary_version_bytes::<Json>(1, &mut Unstructured::new(&data)).unwrap();
+    let versions = peek_versions(&mut serde_json::Deserializer::from_slice(&bytes)).unwrap();
+    assert_eq!(versions.get(std::any::type_name::<Outer>()), Some(&1));
+    assert_eq!(versions.get(std::any::type_name::<Inner>()), Some(&2));
+
+    let value: serde_json::Value = Json::from_bytes(&bytes).unwrap();
+    assert!(value["value"].get("count").is_some());
+    assert!(value["value"].get("total").is_none());
+}
+
+#[test]
+fn test_unsupported_version() {
+    let data = [1u8; 64];
+    assert!(Outer::arbitrary_version_bytes::<Json>(4, &mut Unstructured::new(&data)).is_err());
+}
+
+#[test]
+fn test_fuzz_target() {
+    // Like a `cargo fuzz` target, which is given arbitrary bytes
+    for data in [&[][..], &[0, 1, 2, 3][..], &[255; 300][..]] {
+        let _ = assert_arbitrary_migrates::<Outer, Postcard>(&mut Unstructured::new(data));
+    }
+}
+
+#[test]
+#[should_panic(expected = "to_v2 is broken")]
+fn test_panicking_migration() {
+    assert_random_migrations::<Panics, Json>(100);
+}
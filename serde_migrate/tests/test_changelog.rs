@@ -0,0 +1,89 @@
+use serde_migrate::{versioned, FieldChange, VersionedType};
+
+#[versioned]
+struct Item {
+    pub a: u32,
+    #[version(start = 2, end = 3)]
+    pub b: String,
+    #[version(start = 3)]
+    pub c: u32,
+    #[version(end = 4)]
+    pub value: String,
+    #[version(start = 4)]
+    pub value: u64,
+    #[version(start = 5)]
+    pub d: bool,
+}
+
+impl item_migrations::Migrate for Item {
+    fn to_v2(v: item_migrations::ItemV1) -> item_migrations::ItemV2 {
+        item_migrations::ItemV2 { a: v.a, b: String::new(), value: v.value }
+    }
+
+    fn to_v3(v: item_migrations::ItemV2) -> item_migrations::ItemV3 {
+        item_migrations::ItemV3 { a: v.a, c: v.b.len() as u32, value: v.value }
+    }
+
+    fn to_v4(v: item_migrations::ItemV3) -> item_migrations::ItemV4 {
+        item_migrations::ItemV4 { a: v.a, c: v.c, value: v.value.parse().unwrap_or_default() }
+    }
+
+    fn to_v5(v: item_migrations::ItemV4) -> item_migrations::ItemV5 {
+        item_migrations::ItemV5 { a: v.a, c: v.c, value: v.value, d: false }
+    }
+}
+
+#[versioned(min_supported = 3)]
+struct Recent {
+    #[version(start = 2)]
+    pub a: u32,
+    #[version(start = 4)]
+    pub b: u32,
+}
+
+impl recent_migrations::Migrate for Recent {
+    fn to_v4(v: recent_migrations::RecentV3) -> recent_migrations::RecentV4 {
+        recent_migrations::RecentV4 { a: v.a, b: 0 }
+    }
+}
+
+#[test]
+fn test_changelog_entries() {
+    let changelog = Item::changelog();
+    assert_eq!(changelog.type_key, "test_changelog::Item");
+    assert_eq!(changelog.entries.len(), 5);
+    let v4 = &changelog.entries[3];
+    assert_eq!(v4.version, 4);
+    let [FieldChange::Changed { from, to }] = v4.changes.as_slice() else {
+        panic!("expected a single changed field, got {:?}", v4.changes);
+    };
+    assert_eq!((from.ty, to.ty), ("String", "u64"));
+
+    assert_eq!(changelog.to_string(), "\
+v1: added `a: u32`, added `value: String`
+v2: added `b: String`
+v3: removed `b: String`, added `c: u32`
+v4: changed `value` from `String` to `u64`
+v5: added `d: bool`
+");
+}
+
+#[test]
+fn test_changelog_min_supported() {
+    assert_eq!(Recent::changelog().to_string(), "v3: added `a: u32`\nv4: added `b: u32`\n");
+}
+
+#[test]
+fn test_changelog_markdown() {
+    assert_eq!(Recent::changelog().to_markdown(), "\
+## test_changelog::Recent
+
+### Version 4
+
+* Added `b: u32`
+
+### Version 3
+
+* Added `a: u32`
+");
+}
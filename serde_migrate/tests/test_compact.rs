@@ -0,0 +1,106 @@
+use serde_migrate::{versioned, CompactVersioned, Versioned};
+
+#[versioned(id = 1)]
+#[derive(PartialEq, Debug)]
+struct Message {
+    pub kind: u8,
+    pub items: Vec<Item>,
+}
+
+#[versioned(id = 300)]
+#[derive(PartialEq, Debug)]
+struct Item {
+    #[version(end = 2)]
+    pub old: u32,
+    #[version(start = 2)]
+    pub new: u32,
+}
+
+impl item_migrations::Migrate for Item {
+    fn to_v2(v: item_migrations::ItemV1) -> item_migrations::ItemV2 {
+        item_migrations::ItemV2 { new: v.old }
+    }
+}
+
+#[versioned]
+#[derive(PartialEq, Debug)]
+struct Unregistered {
+    pub value: u32,
+}
+
+fn sample() -> Message {
+    Message {
+        kind: 7,
+        items: vec![Item { new: 1 }, Item { new: 2 }],
+    }
+}
+
+#[test]
+fn test_compact_binary() {
+    let orig = sample();
+
+    let compact = bincode::serialize(&CompactVersioned(&orig)).unwrap();
+    let full = bincode::serialize(&Versioned(&orig)).unwrap();
+    assert!(compact.len() < full.len());
+    let decoded = bincode::deserialize::<CompactVersioned<Message>>(&compact).unwrap().0;
+    assert_eq!(decoded, orig);
+
+    let compact = postcard::to_stdvec(&CompactVersioned(&orig)).unwrap();
+    let full = postcard::to_stdvec(&Versioned(&orig)).unwrap();
+    assert!(compact.len() < full.len());
+    let decoded = postcard::from_bytes::<CompactVersioned<Message>>(&compact).unwrap().0;
+    assert_eq!(decoded, orig);
+}
+
+#[test]
+fn test_compact_json_is_readable() {
+    let orig = sample();
+    let json = serde_json::to_string(&CompactVersioned(&orig)).unwrap();
+    assert!(json.contains("test_compact::Item"));
+    let decoded = serde_json::from_str::<CompactVersioned<Message>>(&json).unwrap().0;
+    assert_eq!(decoded, orig);
+    let decoded = serde_json::from_str::<Versioned<Message>>(&json).unwrap().0;
+    assert_eq!(decoded, orig);
+}
+
+#[test]
+fn test_compact_requires_ids() {
+    let err = bincode::serialize(&CompactVersioned(&Unregistered { value: 1 })).err().unwrap();
+    assert!(err.to_string().contains("has no numeric id"), "{}", err);
+}
+
+#[test]
+fn test_compact_header_encoding() {
+    // Message (id 1) at version 1, and Item (id 300) at version 1, followed by the value
+    let data = [6, 1, 0, 1, 0x2c, 0x01, 1, 7, 1, 99];
+    let decoded = postcard::from_bytes::<CompactVersioned<Message>>(&data).unwrap().0;
+    assert_eq!(decoded, Message {
+        kind: 7,
+        items: vec![Item { new: 99 }],
+    });
+
+    // Item at version 300, which needs a multi-byte varint
+    let mut data = vec![];
+    data.extend_from_slice(&7u64.to_le_bytes());
+    data.extend_from_slice(&[1, 0, 1, 0x2c, 0x01, 0xac, 0x02]);
+    data.push(7);
+    data.extend_from_slice(&1u64.to_le_bytes());
+    data.extend_from_slice(&99u32.to_le_bytes());
+    let err = bincode::deserialize::<CompactVersioned<Message>>(&data).err().unwrap();
+    assert!(err.to_string().contains("Invalid version for Item (got 300)"), "{}", err);
+}
+
+#[test]
+fn test_compact_duplicate_ids() {
+    // Message (id 1) is listed twice in the header
+    let mut data = vec![];
+    data.extend_from_slice(&6u64.to_le_bytes());
+    data.extend_from_slice(&[1, 0, 1, 1, 0, 2]);
+    data.push(7);
+    data.extend_from_slice(&0u64.to_le_bytes());
+    let err = bincode::deserialize::<CompactVersioned<Message>>(&data).err().unwrap();
+    assert!(err.to_string().contains("duplicate type id"), "{}", err);
+
+    // Postcard replaces the error message
+    assert!(postcard::from_bytes::<CompactVersioned<Message>>(&[6, 1, 0, 1, 1, 0, 2, 7, 0]).is_err());
+}
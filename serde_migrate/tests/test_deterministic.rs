@@ -0,0 +1,56 @@
+use serde_migrate::{versioned, Versioned};
+
+#[versioned]
+#[derive(PartialEq, Debug)]
+struct Root {
+    pub c: C,
+    pub a: A,
+    pub b: Vec<B>,
+}
+
+#[versioned]
+#[derive(PartialEq, Debug)]
+struct A {
+    pub value: u32,
+}
+
+#[versioned]
+#[derive(PartialEq, Debug)]
+struct B {
+    pub value: u32,
+}
+
+#[versioned]
+#[derive(PartialEq, Debug)]
+struct C {
+    pub value: u32,
+}
+
+fn sample() -> Root {
+    Root {
+        c: C { value: 3 },
+        a: A { value: 1 },
+        b: vec![B { value: 2 }],
+    }
+}
+
+#[test]
+fn test_deterministic_json() {
+    let value = sample();
+    let json = serde_json::to_string(&Versioned(&value)).unwrap();
+    assert_eq!(json, r#"{"versions":{"test_deterministic::A":1,"test_deterministic::B":1,"test_deterministic::C":1,"test_deterministic::Root":1},"value":{"c":{"value":3},"a":{"value":1},"b":[{"value":2}]}}"#);
+    for _ in 0..100 {
+        assert_eq!(serde_json::to_string(&Versioned(&value)).unwrap(), json);
+    }
+}
+
+#[test]
+fn test_deterministic_binary() {
+    let value = sample();
+    let bc = bincode::serialize(&Versioned(&value)).unwrap();
+    let pc = postcard::to_stdvec(&Versioned(&value)).unwrap();
+    for _ in 0..100 {
+        assert_eq!(bincode::serialize(&Versioned(&value)).unwrap(), bc);
+        assert_eq!(postcard::to_stdvec(&Versioned(&value)).unwrap(), pc);
+    }
+}
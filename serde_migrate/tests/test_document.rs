@@ -0,0 +1,77 @@
+use serde_migrate::{versioned, Schema, SchemaDocument, SchemaMigration, UnknownValue, VersionedDocument};
+
+#[versioned]
+#[derive(PartialEq, Debug)]
+struct Document {
+    pub users: Vec<User>,
+    pub owner: Option<String>,
+}
+
+#[versioned]
+#[derive(PartialEq, Debug)]
+struct User {
+    pub name: String,
+    #[version(start = 2)]
+    pub admin: bool,
+}
+
+impl user_migrations::Migrate for User {
+    fn to_v2(v: user_migrations::UserV1) -> user_migrations::UserV2 {
+        user_migrations::UserV2 {
+            name: v.name,
+            admin: false,
+        }
+    }
+}
+
+impl Schema for Document {
+    const MIGRATIONS: &'static [SchemaMigration] = &[split_users, add_owner];
+}
+
+// Schema version 1 stored the users as a comma-separated string.
+fn split_users(doc: &mut SchemaDocument) -> Result<(), String> {
+    let Some(UnknownValue::String(users)) = doc.value.remove("users") else {
+        return Err("expected users to be a string".to_string());
+    };
+    let users = users.split(',').map(|name| UnknownValue::Map(vec![
+        (UnknownValue::String("name".to_string()), UnknownValue::String(name.to_string())),
+    ])).collect();
+    doc.value.insert("users", UnknownValue::Seq(users));
+    doc.versions.insert("test_document::User".to_string(), 1);
+    Ok(())
+}
+
+fn add_owner(doc: &mut SchemaDocument) -> Result<(), String> {
+    doc.value.insert("owner", UnknownValue::None);
+    Ok(())
+}
+
+#[test]
+fn test_document() {
+    let expected = Document {
+        users: vec![
+            User { name: "a".to_string(), admin: false },
+            User { name: "b".to_string(), admin: false },
+        ],
+        owner: None,
+    };
+
+    let decoded = serde_json::from_str::<VersionedDocument<Document>>(r#"{ "schema": 1, "versions": {}, "value": { "users": "a,b" } }"#).unwrap().0;
+    assert_eq!(decoded, expected);
+
+    let decoded = serde_json::from_str::<VersionedDocument<Document>>(r#"{ "schema": 2, "versions": { "test_document::User": 1 }, "value": { "users": [{ "name": "a" }, { "name": "b" }] } }"#).unwrap().0;
+    assert_eq!(decoded, expected);
+
+    let json = serde_json::to_string(&VersionedDocument(&expected)).unwrap();
+    assert!(json.starts_with(r#"{"schema":3,"#), "{}", json);
+    let decoded = serde_json::from_str::<VersionedDocument<Document>>(&json).unwrap().0;
+    assert_eq!(decoded, expected);
+
+    // Documents at the current schema version do not need a self-describing format
+    let bc = bincode::serialize(&VersionedDocument(&expected)).unwrap();
+    let decoded = bincode::deserialize::<VersionedDocument<Document>>(&bc).unwrap().0;
+    assert_eq!(decoded, expected);
+
+    let err = serde_json::from_str::<VersionedDocument<Document>>(r#"{ "schema": 4, "versions": {}, "value": {} }"#).err().unwrap();
+    assert!(err.to_string().contains("Invalid schema version"), "{}", err);
+}
@@ -0,0 +1,385 @@
+//! Conformance tests for serde formats other than the ones built into the crate.
+//!
+//! Every format is tested with data stored by old versions, with `::` in map keys, and where possible with the header after the value.
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_migrate::{peek_versions, upgrade, versioned, versions_of, Format, FormatError, VersionMap, Versioned};
+
+/// The version header, read with [`peek_versions`] by any format.
+struct Peeked(VersionMap);
+
+impl<'de> Deserialize<'de> for Peeked {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        peek_versions(deserializer).map(Peeked)
+    }
+}
+
+struct Cbor;
+struct MessagePack;
+/// MessagePack with structs serialized as maps instead of arrays.
+struct MessagePackNamed;
+struct Ron;
+struct Toml;
+struct Yaml;
+
+impl Format for Cbor {
+    const NAME: &'static str = "cbor";
+
+    fn to_bytes<T: Serialize + ?Sized>(value: &T) -> Result<Vec<u8>, FormatError> {
+        let mut bytes = vec![];
+        ciborium::into_writer(value, &mut bytes).map_err(FormatError::new)?;
+        Ok(bytes)
+    }
+
+    fn from_bytes<T: serde::de::DeserializeOwned>(input: &[u8]) -> Result<T, FormatError> {
+        ciborium::from_reader(input).map_err(FormatError::new)
+    }
+
+    fn peek_versions(input: &[u8]) -> Result<VersionMap, FormatError> {
+        Self::from_bytes::<Peeked>(input).map(|peeked| peeked.0)
+    }
+}
+
+impl Format for MessagePack {
+    const NAME: &'static str = "msgpack";
+
+    fn to_bytes<T: Serialize + ?Sized>(value: &T) -> Result<Vec<u8>, FormatError> {
+        rmp_serde::to_vec(value).map_err(FormatError::new)
+    }
+
+    fn from_bytes<T: serde::de::DeserializeOwned>(input: &[u8]) -> Result<T, FormatError> {
+        rmp_serde::from_slice(input).map_err(FormatError::new)
+    }
+
+    fn peek_versions(input: &[u8]) -> Result<VersionMap, FormatError> {
+        Self::from_bytes::<Peeked>(input).map(|peeked| peeked.0)
+    }
+}
+
+impl Format for MessagePackNamed {
+    const NAME: &'static str = "msgpack";
+
+    fn to_bytes<T: Serialize + ?Sized>(value: &T) -> Result<Vec<u8>, FormatError> {
+        rmp_serde::to_vec_named(value).map_err(FormatError::new)
+    }
+
+    fn from_bytes<T: serde::de::DeserializeOwned>(input: &[u8]) -> Result<T, FormatError> {
+        rmp_serde::from_slice(input).map_err(FormatError::new)
+    }
+
+    fn peek_versions(input: &[u8]) -> Result<VersionMap, FormatError> {
+        Self::from_bytes::<Peeked>(input).map(|peeked| peeked.0)
+    }
+}
+
+impl Format for Ron {
+    const NAME: &'static str = "ron";
+
+    fn to_bytes<T: Serialize + ?Sized>(value: &T) -> Result<Vec<u8>, FormatError> {
+        ron::to_string(value).map(String::into_bytes).map_err(FormatError::new)
+    }
+
+    fn from_bytes<T: serde::de::DeserializeOwned>(input: &[u8]) -> Result<T, FormatError> {
+        ron::de::from_bytes(input).map_err(FormatError::new)
+    }
+
+    fn peek_versions(input: &[u8]) -> Result<VersionMap, FormatError> {
+        Self::from_bytes::<Peeked>(input).map(|peeked| peeked.0)
+    }
+}
+
+impl Format for Toml {
+    const NAME: &'static str = "toml";
+
+    fn to_bytes<T: Serialize + ?Sized>(value: &T) -> Result<Vec<u8>, FormatError> {
+        toml::to_string(value).map(String::into_bytes).map_err(FormatError::new)
+    }
+
+    fn from_bytes<T: serde::de::DeserializeOwned>(input: &[u8]) -> Result<T, FormatError> {
+        toml::from_str(std::str::from_utf8(input).map_err(FormatError::new)?).map_err(FormatError::new)
+    }
+
+    fn peek_versions(input: &[u8]) -> Result<VersionMap, FormatError> {
+        Self::from_bytes::<Peeked>(input).map(|peeked| peeked.0)
+    }
+}
+
+impl Format for Yaml {
+    const NAME: &'static str = "yaml";
+
+    fn to_bytes<T: Serialize + ?Sized>(value: &T) -> Result<Vec<u8>, FormatError> {
+        serde_yaml::to_string(value).map(String::into_bytes).map_err(FormatError::new)
+    }
+
+    fn from_bytes<T: serde::de::DeserializeOwned>(input: &[u8]) -> Result<T, FormatError> {
+        serde_yaml::from_slice(input).map_err(FormatError::new)
+    }
+
+    fn peek_versions(input: &[u8]) -> Result<VersionMap, FormatError> {
+        Self::from_bytes::<Peeked>(input).map(|peeked| peeked.0)
+    }
+}
+
+#[versioned]
+#[derive(Debug, PartialEq, Clone)]
+struct Inner {
+    pub id: u32,
+    #[version(start = 2)]
+    pub name: String,
+}
+
+impl inner_migrations::Migrate for Inner {
+    fn to_v2(v: inner_migrations::InnerV1) -> inner_migrations::InnerV2 {
+        inner_migrations::InnerV2 { id: v.id, name: format!("inner {}", v.id) }
+    }
+}
+
+#[versioned]
+#[derive(Debug, PartialEq, Clone)]
+struct Outer {
+    #[version(end = 2)]
+    pub count: u32,
+    #[version(start = 2)]
+    pub total: u64,
+    pub inner: Inner,
+    pub others: Vec<Inner>,
+    pub maybe: Option<Inner>,
+    /// Keys that look like the type names in the header
+    pub labels: BTreeMap<String, u32>,
+}
+
+impl outer_migrations::Migrate for Outer {
+    fn to_v2(v: outer_migrations::OuterV1) -> outer_migrations::OuterV2 {
+        outer_migrations::OuterV2 { total: v.count as u64 * 10, inner: v.inner, others: v.others, maybe: v.maybe, labels: v.labels }
+    }
+}
+
+/// The shape of version 1 of `Inner`, which can be serialized.
+#[derive(Serialize)]
+struct InnerV1 {
+    id: u32,
+}
+
+/// The shape of version 1 of `Outer`, which can be serialized.
+#[derive(Serialize)]
+struct OuterV1 {
+    count: u32,
+    inner: InnerV1,
+    others: Vec<InnerV1>,
+    maybe: Option<InnerV1>,
+    labels: BTreeMap<String, u32>,
+}
+
+#[derive(Serialize)]
+#[serde(rename = "Versioned")]
+struct Envelope<V> {
+    versions: VersionMap,
+    value: V,
+}
+
+/// An envelope written by a serializer which does not keep the order of the fields, e.g. one that sorts the keys.
+#[derive(Serialize)]
+#[serde(rename = "Versioned")]
+struct ReversedEnvelope<V> {
+    value: V,
+    versions: VersionMap,
+}
+
+fn labels() -> BTreeMap<String, u32> {
+    BTreeMap::from([("test_formats::Outer".to_owned(), 1), ("a::b::c".to_owned(), 2)])
+}
+
+fn latest() -> Outer {
+    Outer {
+        total: 5,
+        inner: Inner { id: 1, name: "one".to_owned() },
+        others: vec![Inner { id: 2, name: "two".to_owned() }, Inner { id: 3, name: "three".to_owned() }],
+        maybe: Some(Inner { id: 4, name: "four".to_owned() }),
+        labels: labels(),
+    }
+}
+
+fn v1_header() -> VersionMap {
+    VersionMap::from([(std::any::type_name::<Outer>().to_owned(), 1), (std::any::type_name::<Inner>().to_owned(), 1)])
+}
+
+fn v1() -> OuterV1 {
+    OuterV1 {
+        count: 3,
+        inner: InnerV1 { id: 1 },
+        others: vec![InnerV1 { id: 2 }],
+        maybe: None,
+        labels: labels(),
+    }
+}
+
+fn v1_migrated() -> Outer {
+    Outer {
+        total: 30,
+        inner: Inner { id: 1, name: "inner 1".to_owned() },
+        others: vec![Inner { id: 2, name: "inner 2".to_owned() }],
+        maybe: None,
+        labels: labels(),
+    }
+}
+
+fn check_roundtrip<F: Format>() {
+    let value = latest();
+    let bytes = F::to_bytes(&Versioned(&value)).unwrap();
+    let decoded: Versioned<Outer> = F::from_bytes(&bytes).unwrap();
+    assert_eq!(decoded.0, value);
+}
+
+fn check_old_version<F: Format>() {
+    let bytes = F::to_bytes(&Envelope { versions: v1_header(), value: v1() }).unwrap();
+    let decoded: Versioned<Outer> = F::from_bytes(&bytes).unwrap();
+    assert_eq!(decoded.0, v1_migrated());
+}
+
+fn check_header_after_value<F: Format>() {
+    let bytes = F::to_bytes(&ReversedEnvelope { value: v1(), versions: v1_header() }).unwrap();
+    let decoded: Versioned<Outer> = F::from_bytes(&bytes).unwrap();
+    assert_eq!(decoded.0, v1_migrated());
+
+    let bytes = F::to_bytes(&ReversedEnvelope { value: latest(), versions: versions_of(&latest()) }).unwrap();
+    let decoded: Versioned<Outer> = F::from_bytes(&bytes).unwrap();
+    assert_eq!(decoded.0, latest());
+}
+
+fn check_peek<F: Format>() {
+    let bytes = F::to_bytes(&Envelope { versions: v1_header(), value: v1() }).unwrap();
+    let versions = F::peek_versions(&bytes).unwrap();
+    assert_eq!(versions, v1_header());
+}
+
+fn check_upgrade<F: Format>() {
+    let bytes = F::to_bytes(&Envelope { versions: v1_header(), value: v1() }).unwrap();
+    let mut upgraded = vec![];
+    let report = upgrade::<Outer, F>(&bytes, &mut upgraded).unwrap();
+    assert!(report.changed);
+    assert_eq!(upgraded, F::to_bytes(&Versioned(v1_migrated())).unwrap());
+}
+
+macro_rules! conformance_tests {
+    ($($module:ident: $format:ty,)*) => {
+        $(
+            mod $module {
+                use super::*;
+
+                #[test]
+                fn roundtrip() {
+                    check_roundtrip::<$format>();
+                }
+
+                #[test]
+                fn old_version() {
+                    check_old_version::<$format>();
+                }
+
+                #[test]
+                fn peek() {
+                    check_peek::<$format>();
+                }
+
+                #[test]
+                fn upgrade() {
+                    check_upgrade::<$format>();
+                }
+            }
+        )*
+    };
+}
+
+// The tests are in a module, so that the modules of each format do not shadow the crates of the same name
+mod formats {
+    use super::*;
+
+    conformance_tests! {
+        cbor: Cbor,
+        msgpack: MessagePack,
+        msgpack_named: MessagePackNamed,
+        ron: Ron,
+        toml: Toml,
+        yaml: Yaml,
+    }
+}
+
+/// The header can only come after the value in formats where structs are maps.
+mod header_after_value {
+    use super::*;
+
+    #[test]
+    fn cbor() {
+        check_header_after_value::<Cbor>();
+    }
+
+    #[test]
+    fn msgpack_named() {
+        check_header_after_value::<MessagePackNamed>();
+    }
+
+    #[test]
+    fn ron() {
+        check_header_after_value::<Ron>();
+    }
+
+    #[test]
+    fn toml() {
+        check_header_after_value::<Toml>();
+    }
+
+    #[test]
+    fn yaml() {
+        check_header_after_value::<Yaml>();
+    }
+
+    #[test]
+    fn json() {
+        check_header_after_value::<serde_migrate::Json>();
+    }
+
+    #[versioned(preserve_unknown)]
+    #[derive(Debug, PartialEq)]
+    struct Preserving {
+        pub a: u32,
+    }
+
+    #[test]
+    fn json_value_with_unknown_fields() {
+        // The keys of a json value are sorted, so "value" comes before "versions"
+        let json = serde_json::json!({
+            "versions": { std::any::type_name::<Preserving>(): 1 },
+            "value": { "a": 1, "b": [1, 2] },
+        });
+        let bytes = serde_json::to_vec(&json).unwrap();
+        let decoded: Versioned<Preserving> = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(decoded.0.a, 1);
+        assert_eq!(serde_json::to_value(&decoded.0).unwrap(), serde_json::json!({ "a": 1, "b": [1, 2] }));
+    }
+
+    #[versioned]
+    #[derive(Debug, PartialEq)]
+    struct Host {
+        /// Serialized as a string by human-readable formats, and as bytes by others
+        pub address: std::net::IpAddr,
+    }
+
+    fn check_not_human_readable<F: Format>() {
+        let host = Host { address: std::net::Ipv4Addr::new(10, 0, 0, 1).into() };
+        let bytes = F::to_bytes(&ReversedEnvelope { value: &host, versions: versions_of(&host) }).unwrap();
+        let decoded: Versioned<Host> = F::from_bytes(&bytes).unwrap();
+        assert_eq!(decoded.0, host);
+    }
+
+    #[test]
+    fn cbor_not_human_readable() {
+        check_not_human_readable::<Cbor>();
+    }
+
+    #[test]
+    fn msgpack_not_human_readable() {
+        check_not_human_readable::<MessagePackNamed>();
+    }
+}
@@ -0,0 +1,71 @@
+#![cfg(feature = "schemars")]
+
+use serde_json::json;
+use serde_migrate::{schemars::schema_for, versioned, Versioned};
+
+#[versioned(json_schema)]
+#[serde(rename_all = "camelCase")]
+struct Inner {
+    pub inner_id: u32,
+    #[version(start = 2)]
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+impl inner_migrations::Migrate for Inner {
+    fn to_v2(v: inner_migrations::InnerV1) -> inner_migrations::InnerV2 {
+        inner_migrations::InnerV2 { inner_id: v.inner_id, tags: vec![] }
+    }
+}
+
+#[versioned(json_schema, min_supported = 2)]
+struct Outer {
+    #[version(end = 3)]
+    pub name: String,
+    #[version(start = 3)]
+    pub label: String,
+    pub inner: Inner,
+}
+
+impl outer_migrations::Migrate for Outer {
+    fn to_v3(v: outer_migrations::OuterV2) -> outer_migrations::OuterV3 {
+        outer_migrations::OuterV3 { label: v.name, inner: v.inner }
+    }
+}
+
+#[test]
+fn test_schema_for_version() {
+    let v1 = Inner::json_schema_for_version(1).unwrap();
+    assert_eq!(v1.get("properties").unwrap(), &json!({
+        "innerId": { "type": "integer", "format": "uint32", "minimum": 0 },
+    }));
+    assert_eq!(v1.get("required").unwrap(), &json!(["innerId"]));
+
+    let v2 = Inner::json_schema_for_version(2).unwrap();
+    assert!(v2.get("properties").unwrap().get("tags").is_some());
+    assert_eq!(v2.get("required").unwrap(), &json!(["innerId"]));
+
+    assert!(Inner::json_schema_for_version(3).is_none());
+    assert!(Outer::json_schema_for_version(1).is_none());
+    let v2 = Outer::json_schema_for_version(2).unwrap();
+    assert_eq!(v2.get("required").unwrap(), &json!(["name", "inner"]));
+}
+
+#[test]
+fn test_latest_schema() {
+    // The type has the schema of its latest version, and nested versioned types are referenced by their latest version
+    let schema = schema_for!(Outer);
+    assert_eq!(schema.get("required").unwrap(), &json!(["label", "inner"]));
+    assert_eq!(schema.get("properties").unwrap()["inner"], json!({ "$ref": "#/$defs/InnerV2" }));
+    assert!(schema.get("$defs").unwrap().get("InnerV2").is_some());
+}
+
+#[test]
+fn test_envelope_schema() {
+    let schema = schema_for!(Versioned<Outer>);
+    assert_eq!(schema.get("required").unwrap(), &json!(["versions", "value"]));
+    let properties = schema.get("properties").unwrap();
+    assert_eq!(properties["versions"]["type"], "object");
+    assert_eq!(properties["versions"]["additionalProperties"]["type"], "integer");
+    assert_eq!(properties["value"], json!({ "$ref": "#/$defs/OuterV3" }));
+}
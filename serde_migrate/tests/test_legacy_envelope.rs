@@ -0,0 +1,70 @@
+use serde_migrate::{versioned, Versioned};
+
+#[versioned]
+#[derive(PartialEq, Debug)]
+struct MyStruct {
+    pub a: u32,
+    #[version(start = 2, end = 3)]
+    pub b: String,
+    #[version(start = 3)]
+    pub c: u32,
+    pub inner: Inner,
+}
+
+#[versioned]
+#[derive(PartialEq, Debug)]
+struct Inner {
+    #[version(end = 2)]
+    pub old: u32,
+    #[version(start = 2)]
+    pub new: u32,
+}
+
+impl mystruct_migrations::Migrate for MyStruct {
+    fn to_v2(v: mystruct_migrations::MyStructV1) -> mystruct_migrations::MyStructV2 {
+        mystruct_migrations::MyStructV2 {
+            a: v.a,
+            b: "321".to_string(),
+            inner: v.inner,
+        }
+    }
+
+    fn to_v3(v: mystruct_migrations::MyStructV2) -> mystruct_migrations::MyStructV3 {
+        mystruct_migrations::MyStructV3 {
+            a: v.a,
+            c: v.b.parse().unwrap(),
+            inner: v.inner,
+        }
+    }
+}
+
+impl inner_migrations::Migrate for Inner {
+    fn to_v2(v: inner_migrations::InnerV1) -> inner_migrations::InnerV2 {
+        inner_migrations::InnerV2 {
+            new: v.old,
+        }
+    }
+}
+
+#[test]
+fn test_legacy_envelope() {
+    let expected = MyStruct {
+        a: 123,
+        c: 321,
+        inner: Inner { new: 5 },
+    };
+
+    // The version applies to the root type, and nested types are treated as version 1
+    let decoded = serde_json::from_str::<Versioned<MyStruct>>(r#"{ "version": 1, "value": { "a": 123, "inner": { "old": 5 } } }"#).unwrap().0;
+    assert_eq!(decoded, expected);
+
+    let decoded = serde_json::from_str::<Versioned<MyStruct>>(r#"{ "version": 3, "value": { "a": 123, "c": 321, "inner": { "old": 5 } } }"#).unwrap().0;
+    assert_eq!(decoded, expected);
+
+    // The current format is always written
+    let json = serde_json::to_string(&Versioned(&decoded)).unwrap();
+    assert!(json.starts_with(r#"{"versions":{"#), "{}", json);
+
+    let err = serde_json::from_str::<Versioned<MyStruct>>(r#"{ "version": 1, "versions": {}, "value": {} }"#).err().unwrap();
+    assert!(err.to_string().contains("Duplicate field"), "{}", err);
+}
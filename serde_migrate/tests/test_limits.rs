@@ -0,0 +1,185 @@
+use serde_migrate::{peek_versions, versioned, Bincode, CompactVersioned, Format, HeaderLimits, Json, MigrationRegistry, Postcard, Versioned, VersionedLogReader};
+
+#[versioned(id = 1)]
+#[derive(PartialEq, Debug)]
+struct Message {
+    #[version(end = 2)]
+    pub old: u32,
+    #[version(start = 2)]
+    pub new: u32,
+    pub items: Vec<Item>,
+}
+
+impl message_migrations::Migrate for Message {
+    fn to_v2(v: message_migrations::MessageV1) -> message_migrations::MessageV2 {
+        message_migrations::MessageV2 { new: v.old, items: v.items }
+    }
+}
+
+#[versioned(id = 2)]
+#[derive(PartialEq, Debug)]
+struct Item {
+    pub name: String,
+}
+
+fn sample() -> Message {
+    Message { new: 1, items: vec![Item { name: "a".to_owned() }] }
+}
+
+/// A json envelope with the given header entries, and a value of version 2 of `Message`.
+fn json_with_header(entries: impl IntoIterator<Item = (String, u32)>) -> String {
+    let header = entries.into_iter().map(|(k, v)| format!("{:?}: {}", k, v)).collect::<Vec<_>>().join(", ");
+    format!(r#"{{ "versions": {{ {} }}, "value": {{ "new": 1, "items": [] }} }}"#, header)
+}
+
+fn message_entry(version: u32) -> (String, u32) {
+    (std::any::type_name::<Message>().to_owned(), version)
+}
+
+#[test]
+fn test_too_many_entries() {
+    let entries = (0..2000).map(|i| (format!("t{}", i), 1)).chain([message_entry(2)]);
+    let err = serde_json::from_str::<Versioned<Message>>(&json_with_header(entries)).err().unwrap();
+    assert!(err.to_string().contains("The version header has more than 1024 entries"), "{}", err);
+
+    let limits = HeaderLimits { max_entries: 2, ..HeaderLimits::DEFAULT };
+    let json = json_with_header([message_entry(2), ("a".to_owned(), 1)]);
+    assert_eq!(limits.scope(|| serde_json::from_str::<Versioned<Message>>(&json)).unwrap().0.new, 1);
+    let json = json_with_header([message_entry(2), ("a".to_owned(), 1), ("b".to_owned(), 1)]);
+    assert!(limits.scope(|| serde_json::from_str::<Versioned<Message>>(&json)).is_err());
+    // The limits are restored after the scope
+    assert_eq!(HeaderLimits::current(), HeaderLimits::DEFAULT);
+    assert!(serde_json::from_str::<Versioned<Message>>(&json).is_ok());
+}
+
+#[test]
+fn test_hostile_length_prefix() {
+    // A bincode map claiming to have u64::MAX entries is rejected before any entry is read
+    let bytes = u64::MAX.to_le_bytes();
+    let err = Bincode::from_bytes::<Versioned<Message>>(&bytes).err().unwrap();
+    assert!(err.to_string().contains("more than 1024 entries"), "{}", err);
+}
+
+#[test]
+fn test_long_key() {
+    let err = serde_json::from_str::<Versioned<Message>>(&json_with_header([("a".repeat(2000), 1), message_entry(2)])).err().unwrap();
+    assert!(err.to_string().contains("A type name in the version header is longer than 1024 bytes"), "{}", err);
+
+    let limits = HeaderLimits { max_key_length: 4000, ..HeaderLimits::DEFAULT };
+    assert!(limits.scope(|| serde_json::from_str::<Versioned<Message>>(&json_with_header([("a".repeat(2000), 1), message_entry(2)]))).is_ok());
+}
+
+#[test]
+fn test_max_version() {
+    let limits = HeaderLimits { max_version: 10, ..HeaderLimits::DEFAULT };
+    let err = limits.scope(|| serde_json::from_str::<Versioned<Message>>(&json_with_header([message_entry(2), ("a".to_owned(), 11)]))).err().unwrap();
+    assert!(err.to_string().contains("Version 11 in the version header is higher than the limit of 10"), "{}", err);
+
+    let legacy = r#"{ "version": 11, "value": { "new": 1, "items": [] } }"#;
+    assert!(limits.scope(|| serde_json::from_str::<Versioned<Message>>(legacy)).is_err());
+}
+
+#[test]
+fn test_duplicate_keys() {
+    let err = serde_json::from_str::<Versioned<Message>>(&json_with_header([message_entry(1), message_entry(2)])).err().unwrap();
+    assert!(err.to_string().contains("Duplicate type test_limits::Message in the version header"), "{}", err);
+
+    let err = peek_versions(&mut serde_json::Deserializer::from_str(&json_with_header([message_entry(1), message_entry(1)]))).err().unwrap();
+    assert!(err.to_string().contains("Duplicate type"), "{}", err);
+}
+
+#[test]
+fn test_limits_apply_to_peek() {
+    let limits = HeaderLimits { max_entries: 1, ..HeaderLimits::DEFAULT };
+    let bytes = Json::to_bytes(&Versioned(sample())).unwrap();
+    assert!(Json::peek_versions(&bytes).is_ok());
+    assert!(limits.scope(|| Json::peek_versions(&bytes)).is_err());
+}
+
+#[test]
+fn test_compact_header() {
+    // Two entries for type id 1
+    let header = vec![1u8, 0, 1, 1, 0, 2];
+    let err = bincode::deserialize::<CompactVersioned<Message>>(&bincode::serialize(&(header, (1u32, Vec::<String>::new()))).unwrap()).err().unwrap();
+    assert!(err.to_string().contains("duplicate type id 1"), "{}", err);
+
+    let bytes = bincode::serialize(&CompactVersioned(sample())).unwrap();
+    let limits = HeaderLimits { max_entries: 1, ..HeaderLimits::DEFAULT };
+    let err = limits.scope(|| bincode::deserialize::<CompactVersioned<Message>>(&bytes)).err().unwrap();
+    assert!(err.to_string().contains("more than 1 entries"), "{}", err);
+}
+
+#[test]
+fn test_limits_apply_to_log_and_registry() {
+    let limits = HeaderLimits { max_entries: 1, ..HeaderLimits::DEFAULT };
+    let log = format!("{{\"Header\":{{\"{}\":2,\"a\":1}}}}\n{{\"Record\":{{\"new\":1,\"items\":[]}}}}\n", std::any::type_name::<Message>());
+    let read = || VersionedLogReader::<_, Message, Json>::new(log.as_bytes()).collect::<Vec<_>>();
+    assert!(read()[0].is_ok());
+    // The rejected header is reported, and the record after it has no header
+    let records = limits.scope(read);
+    assert_eq!(records.len(), 2);
+    assert!(records[1].is_err());
+    assert!(records[0].as_ref().err().unwrap().error.to_string().contains("more than 1 entries"));
+
+    let mut registry = MigrationRegistry::new();
+    registry.register::<Message>();
+    let value: serde_json::Value = serde_json::from_str(&json_with_header([message_entry(2), ("a".to_owned(), 1)])).unwrap();
+    let migrate = || registry.migrate_json(std::any::type_name::<Message>(), value.clone());
+    assert!(migrate().is_ok());
+    let err = limits.scope(migrate).err().unwrap();
+    assert!(err.to_string().contains("more than 1 entries"), "{}", err);
+}
+
+#[test]
+fn test_scope_restores_limits_after_panic() {
+    let limits = HeaderLimits { max_entries: 1, ..HeaderLimits::DEFAULT };
+    assert!(std::panic::catch_unwind(|| limits.scope(|| panic!("in scope"))).is_err());
+    assert_eq!(HeaderLimits::current(), HeaderLimits::DEFAULT);
+}
+
+/// Deserializes `input` with every format and envelope. Errors are fine, but nothing may panic.
+fn read_everything(input: &[u8]) {
+    let _ = Json::from_bytes::<Versioned<Message>>(input);
+    let _ = Bincode::from_bytes::<Versioned<Message>>(input);
+    let _ = Postcard::from_bytes::<Versioned<Message>>(input);
+    let _ = bincode::deserialize::<CompactVersioned<Message>>(input);
+    let _ = postcard::from_bytes::<CompactVersioned<Message>>(input);
+    let _ = Json::peek_versions(input);
+    let _ = Bincode::peek_versions(input);
+    let _ = Postcard::peek_versions(input);
+}
+
+#[test]
+fn test_mutated_input_does_not_panic() {
+    let v1 = json_with_header([message_entry(1), (std::any::type_name::<Item>().to_owned(), 1)]).replace("\"new\"", "\"old\"");
+    let valid = [
+        v1.into_bytes(),
+        Json::to_bytes(&Versioned(sample())).unwrap(),
+        Bincode::to_bytes(&Versioned(sample())).unwrap(),
+        Postcard::to_bytes(&Versioned(sample())).unwrap(),
+        bincode::serialize(&CompactVersioned(sample())).unwrap(),
+        postcard::to_stdvec(&CompactVersioned(sample())).unwrap(),
+    ];
+
+    let mut state = 0x9e37_79b9_7f4a_7c15_u64;
+    let mut next = move || {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        state
+    };
+    for input in &valid {
+        read_everything(input);
+        for len in 0..input.len() {
+            read_everything(&input[..len]);
+        }
+        for _ in 0..2000 {
+            let mut mutated = input.clone();
+            for _ in 0..(next() % 4 + 1) {
+                let i = (next() % mutated.len() as u64) as usize;
+                mutated[i] = next() as u8;
+            }
+            read_everything(&mutated);
+        }
+    }
+}
@@ -0,0 +1,107 @@
+use serde_migrate::{versioned, Format, Json, Postcard, RecordError, VersionedLogReader, VersionedLogWriter};
+
+#[versioned]
+#[derive(PartialEq, Debug, Clone)]
+struct Event {
+    pub id: u32,
+    #[version(end = 2)]
+    pub name: String,
+    #[version(start = 2)]
+    pub label: String,
+}
+
+impl event_migrations::Migrate for Event {
+    fn to_v2(v: event_migrations::EventV1) -> event_migrations::EventV2 {
+        event_migrations::EventV2 { id: v.id, label: v.name }
+    }
+}
+
+#[versioned]
+#[derive(PartialEq, Debug, Clone)]
+struct Tag {
+    pub name: String,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, PartialEq, Debug, Clone)]
+struct Entry {
+    event: Event,
+    tag: Option<Tag>,
+}
+
+fn entry(id: u32, tag: Option<&str>) -> Entry {
+    Entry {
+        event: Event { id, label: format!("event {}", id) },
+        tag: tag.map(|name| Tag { name: name.to_string() }),
+    }
+}
+
+fn count_headers<F: Format>(log: &[u8], is_header: impl Fn(&[u8]) -> bool) -> usize {
+    let mut reader = std::io::BufReader::new(log);
+    let mut buf = Vec::new();
+    let mut headers = 0;
+    while F::read_record(&mut reader, &mut buf).unwrap() {
+        if is_header(&buf) {
+            headers += 1;
+        }
+    }
+    headers
+}
+
+fn roundtrip<F: Format>(is_header: impl Fn(&[u8]) -> bool) {
+    let entries = vec![entry(1, None), entry(2, None), entry(3, Some("a")), entry(4, Some("b")), entry(5, None)];
+    let mut writer = VersionedLogWriter::<_, F>::new(Vec::new());
+    for e in &entries {
+        writer.append(e).unwrap();
+    }
+    assert_eq!(writer.header().unwrap().len(), 2);
+    let log = writer.into_inner();
+
+    // One header at the start, and one when Tag first appears
+    assert_eq!(count_headers::<F>(&log, is_header), 2, "{}", F::NAME);
+
+    let read: Vec<Entry> = VersionedLogReader::<_, _, F>::new(&log[..]).map(Result::unwrap).collect();
+    assert_eq!(read, entries);
+}
+
+#[test]
+fn test_log_json() {
+    roundtrip::<Json>(|entry| entry.starts_with(br#"{"Header""#));
+}
+
+#[test]
+fn test_log_postcard() {
+    // The variant index of the header
+    roundtrip::<Postcard>(|entry| entry[0] == 0);
+}
+
+#[test]
+fn test_log_migrates_records() {
+    let log = br#"{"Header":{"test_log::Event":1}}
+{"Record":{"id":1,"name":"a"}}
+{"Record":{"id":2,"name":"b"}}
+{"Header":{"test_log::Event":2}}
+{"Record":{"id":3,"label":"c"}}
+"#;
+    let mut reader = VersionedLogReader::<_, Event, Json>::new(&log[..]);
+    assert_eq!(reader.next().unwrap().unwrap(), Event { id: 1, label: "a".to_string() });
+    assert_eq!(reader.header().unwrap()["test_log::Event"], 1);
+    assert_eq!(reader.next().unwrap().unwrap(), Event { id: 2, label: "b".to_string() });
+    assert_eq!(reader.next().unwrap().unwrap(), Event { id: 3, label: "c".to_string() });
+    assert_eq!(reader.header().unwrap()["test_log::Event"], 2);
+    assert!(reader.next().is_none());
+}
+
+#[test]
+fn test_log_errors() {
+    let log = br#"{"Record":{"id":1,"name":"a"}}
+{"Header":{"test_log::Event":2}}
+{"Record":{"id":2}}
+{"Record":{"id":3,"label":"c"}}
+"#;
+    let records: Vec<Result<Event, RecordError>> = VersionedLogReader::<_, _, Json>::new(&log[..]).collect();
+    assert_eq!(records.len(), 3);
+    assert_eq!(records[0].as_ref().unwrap_err().index, 0);
+    assert!(records[0].as_ref().unwrap_err().to_string().contains("before the first header"));
+    assert_eq!(records[1].as_ref().unwrap_err().index, 1);
+    assert_eq!(records[2].as_ref().unwrap(), &Event { id: 3, label: "c".to_string() });
+}
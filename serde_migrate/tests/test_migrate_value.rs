@@ -0,0 +1,67 @@
+use serde_migrate::{versioned, versions_of, MigrateValue, VersionMap};
+use serde_json::json;
+
+#[versioned]
+#[derive(PartialEq, Debug)]
+struct Inner {
+    #[version(end = 2)]
+    pub name: String,
+    #[version(start = 2)]
+    pub names: Vec<String>,
+}
+
+impl inner_migrations::Migrate for Inner {
+    fn to_v2(v: inner_migrations::InnerV1) -> inner_migrations::InnerV2 {
+        inner_migrations::InnerV2 { names: vec![v.name] }
+    }
+}
+
+#[versioned]
+#[derive(PartialEq, Debug)]
+struct Outer {
+    pub inner: Inner,
+    #[version(start = 2)]
+    pub count: u32,
+}
+
+impl outer_migrations::Migrate for Outer {
+    fn to_v2(v: outer_migrations::OuterV1) -> outer_migrations::OuterV2 {
+        outer_migrations::OuterV2 { inner: v.inner, count: 1 }
+    }
+}
+
+fn versions(v: &[(&str, u32)]) -> VersionMap {
+    v.iter().map(|(name, version)| (name.to_string(), *version)).collect()
+}
+
+#[test]
+fn test_migrate_value_nested() {
+    let value = json!({ "inner": { "name": "a" } });
+    let migrated = Outer::migrate_value(value, &versions(&[("test_migrate_value::Outer", 1), ("test_migrate_value::Inner", 1)])).unwrap();
+    assert_eq!(migrated, json!({ "inner": { "names": ["a"] }, "count": 1 }));
+
+    // Only the inner type is old
+    let value = json!({ "inner": { "name": "b" }, "count": 5 });
+    let migrated = Outer::migrate_value(value, &versions(&[("test_migrate_value::Outer", 2), ("test_migrate_value::Inner", 1)])).unwrap();
+    assert_eq!(migrated, json!({ "inner": { "names": ["b"] }, "count": 5 }));
+
+    // Missing types are treated as version 1
+    let migrated = Inner::migrate_value(json!({ "name": "c" }), &VersionMap::new()).unwrap();
+    assert_eq!(migrated, json!({ "names": ["c"] }));
+}
+
+#[test]
+fn test_migrate_value_latest() {
+    let latest = Outer { inner: Inner { names: vec!["a".to_string()] }, count: 2 };
+    let value = serde_json::to_value(&latest).unwrap();
+    assert_eq!(Outer::migrate_value(value.clone(), &versions_of(&latest)).unwrap(), value);
+}
+
+#[test]
+fn test_migrate_value_errors() {
+    let err = Inner::migrate_value(json!({ "names": ["a"] }), &versions(&[("test_migrate_value::Inner", 3)])).unwrap_err();
+    assert_eq!(err.to_string(), "Invalid version for Inner (got 3)");
+
+    let err = Inner::migrate_value(json!({ "names": ["a"] }), &VersionMap::new()).unwrap_err();
+    assert!(err.to_string().contains("name"), "{}", err);
+}
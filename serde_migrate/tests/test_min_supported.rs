@@ -0,0 +1,45 @@
+use serde_migrate::{versioned, Versioned};
+
+// History before version 3 has been squashed.
+#[versioned(min_supported = 3)]
+#[derive(PartialEq, Debug)]
+struct A {
+    pub a: u32,
+    #[version(start = 2)]
+    pub b: u32,
+    #[version(end = 4)]
+    pub old: u32,
+    #[version(start = 4)]
+    pub c: u32,
+}
+
+impl a_migrations::Migrate for A {
+    fn to_v4(v: a_migrations::AV3) -> a_migrations::AV4 {
+        a_migrations::AV4 {
+            a: v.a,
+            b: v.b,
+            c: v.old + 1,
+        }
+    }
+}
+
+#[test]
+fn test_min_supported() {
+    let decoded = serde_json::from_str::<Versioned<A>>(r#"{ "versions": { "test_min_supported::A": 3 }, "value": { "a": 1, "b": 2, "old": 3 } }"#).unwrap().0;
+    assert_eq!(decoded, A { a: 1, b: 2, c: 4 });
+
+    let err = serde_json::from_str::<Versioned<A>>(r#"{ "versions": { "test_min_supported::A": 2 }, "value": { "a": 1, "b": 2, "old": 3 } }"#).err().unwrap();
+    assert!(err.to_string().contains("too old"), "{}", err);
+
+    // Data without any version information is treated as version 1
+    let err = serde_json::from_str::<Versioned<A>>(r#"{ "versions": {}, "value": { "a": 1 } }"#).err().unwrap();
+    assert!(err.to_string().contains("too old"), "{}", err);
+}
+
+#[test]
+fn test_migrate_to_latest() {
+    let mut out = Vec::new();
+    let mut de = serde_json::Deserializer::from_str(r#"{ "versions": { "test_min_supported::A": 3 }, "value": { "a": 1, "b": 2, "old": 3 } }"#);
+    serde_migrate::migrate_to_latest::<A, _, _>(&mut de, &mut serde_json::Serializer::new(&mut out)).unwrap();
+    assert_eq!(String::from_utf8(out).unwrap(), r#"{"versions":{"test_min_supported::A":4},"value":{"a":1,"b":2,"c":4}}"#);
+}
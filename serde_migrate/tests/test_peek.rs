@@ -0,0 +1,71 @@
+use bincode::Options;
+use serde_migrate::{versioned, Versioned, VersionMap};
+
+#[versioned]
+#[derive(PartialEq, Debug)]
+struct Outer {
+    pub inner: Vec<Inner>,
+    #[version(start = 2)]
+    pub name: String,
+}
+
+#[versioned]
+#[derive(PartialEq, Debug)]
+struct Inner {
+    pub value: u32,
+}
+
+impl outer_migrations::Migrate for Outer {
+    fn to_v2(v: outer_migrations::OuterV1) -> outer_migrations::OuterV2 {
+        outer_migrations::OuterV2 {
+            inner: v.inner,
+            name: String::new(),
+        }
+    }
+}
+
+fn expected() -> VersionMap {
+    [("test_peek::Outer".to_string(), 2), ("test_peek::Inner".to_string(), 1)].into_iter().collect()
+}
+
+fn sample() -> Outer {
+    Outer {
+        inner: vec![Inner { value: 1 }, Inner { value: 2 }],
+        name: "hello".to_string(),
+    }
+}
+
+#[test]
+fn test_peek_json() {
+    let json = serde_json::to_string(&Versioned(&sample())).unwrap();
+    let versions = serde_migrate::peek_versions(&mut serde_json::Deserializer::from_str(&json)).unwrap();
+    assert_eq!(versions, expected());
+
+    // The value may come before the header
+    let json = r#"{ "value": { "inner": [], "name": "x" }, "versions": { "test_peek::Outer": 2 } }"#;
+    let versions = serde_migrate::peek_versions(&mut serde_json::Deserializer::from_str(json)).unwrap();
+    assert_eq!(versions, [("test_peek::Outer".to_string(), 2)].into_iter().collect());
+
+    let json = r#"{ "version": 1, "value": {} }"#;
+    assert!(serde_migrate::peek_versions(&mut serde_json::Deserializer::from_str(json)).is_err());
+}
+
+#[test]
+fn test_peek_bincode() {
+    let bc = bincode::serialize(&Versioned(&sample())).unwrap();
+    let options = bincode::DefaultOptions::new().with_fixint_encoding().allow_trailing_bytes();
+    let versions = serde_migrate::peek_versions(&mut bincode::Deserializer::from_slice(&bc, options)).unwrap();
+    assert_eq!(versions, expected());
+
+    // The value is not validated, so a truncated value is not an error
+    let truncated = &bc[..bc.len() - 3];
+    let versions = serde_migrate::peek_versions(&mut bincode::Deserializer::from_slice(truncated, options)).unwrap();
+    assert_eq!(versions, expected());
+}
+
+#[test]
+fn test_peek_postcard() {
+    let pc = postcard::to_stdvec(&Versioned(&sample())).unwrap();
+    let versions = serde_migrate::peek_versions(&mut postcard::Deserializer::from_bytes(&pc)).unwrap();
+    assert_eq!(versions, expected());
+}
@@ -0,0 +1,66 @@
+use serde_migrate::{versioned, Versioned, UnknownValue};
+
+#[versioned(preserve_unknown)]
+#[derive(PartialEq, Debug)]
+struct Doc {
+    pub a: u32,
+    #[version(start = 2)]
+    pub b: String,
+}
+
+impl doc_migrations::Migrate for Doc {
+    fn to_v2(v: doc_migrations::DocV1) -> doc_migrations::DocV2 {
+        doc_migrations::DocV2 {
+            a: v.a,
+            b: v.a.to_string(),
+        }
+    }
+}
+
+#[test]
+fn test_preserve_unknown() {
+    // Written by a newer binary, where Doc is at version 3 and has gained a field `c`
+    let json = r#"{"versions":{"test_preserve_unknown::Doc":3},"value":{"a":1,"b":"x","c":{"nested":[1,2.5,null]}}}"#;
+    let mut decoded = serde_json::from_str::<Versioned<Doc>>(json).unwrap().0;
+    assert_eq!(decoded.a, 1);
+    assert_eq!(decoded.b, "x");
+    assert_eq!(decoded.unknown_fields.stored_version(), Some(3));
+    assert_eq!(decoded.unknown_fields.len(), 1);
+    assert!(matches!(decoded.unknown_fields.get("c"), Some(UnknownValue::Map(_))));
+
+    decoded.a = 2;
+    let encoded = serde_json::to_string(&Versioned(&decoded)).unwrap();
+    assert_eq!(encoded, r#"{"versions":{"test_preserve_unknown::Doc":3},"value":{"a":2,"b":"x","c":{"nested":[1,2.5,null]}}}"#);
+}
+
+#[test]
+fn test_preserve_unknown_migrates_older_data() {
+    let json = r#"{"versions":{"test_preserve_unknown::Doc":1},"value":{"a":1}}"#;
+    let decoded = serde_json::from_str::<Versioned<Doc>>(json).unwrap().0;
+    assert_eq!(decoded, Doc {
+        a: 1,
+        b: "1".to_string(),
+        unknown_fields: Default::default(),
+    });
+
+    let encoded = serde_json::to_string(&Versioned(&decoded)).unwrap();
+    assert_eq!(encoded, r#"{"versions":{"test_preserve_unknown::Doc":2},"value":{"a":1,"b":"1"}}"#);
+}
+
+#[test]
+fn test_preserve_unknown_binary_formats() {
+    let doc = Doc { a: 1, b: "x".to_string(), unknown_fields: Default::default() };
+    let bytes = bincode::serialize(&Versioned(&doc)).unwrap();
+    assert_eq!(bincode::deserialize::<Versioned<Doc>>(&bytes).unwrap().0, doc);
+    let bytes = postcard::to_stdvec(&Versioned(&doc)).unwrap();
+    assert_eq!(postcard::from_bytes::<Versioned<Doc>>(&bytes).unwrap().0, doc);
+
+    // Older versions are migrated as usual
+    let v1 = bincode::serialize(&(vec![("test_preserve_unknown::Doc", 1u32)], 5u32)).unwrap();
+    assert_eq!(bincode::deserialize::<Versioned<Doc>>(&v1).unwrap().0, Doc { a: 5, b: "5".to_string(), unknown_fields: Default::default() });
+
+    // Unknown fields cannot be written without a self-describing format
+    let json = r#"{"versions":{"test_preserve_unknown::Doc":3},"value":{"a":1,"b":"x","c":1}}"#;
+    let decoded = serde_json::from_str::<Versioned<Doc>>(json).unwrap().0;
+    assert!(bincode::serialize(&Versioned(&decoded)).is_err());
+}
@@ -0,0 +1,141 @@
+use serde_migrate::{versioned, Bincode, Format, FormatError, Json, MigrationRegistry, Postcard, VersionMap, Versioned};
+
+#[versioned]
+#[derive(PartialEq, Debug)]
+struct Event {
+    pub id: u32,
+    #[version(end = 2)]
+    pub name: String,
+    #[version(start = 2)]
+    pub label: String,
+}
+
+impl event_migrations::Migrate for Event {
+    fn to_v2(v: event_migrations::EventV1) -> event_migrations::EventV2 {
+        event_migrations::EventV2 { id: v.id, label: v.name }
+    }
+}
+
+#[versioned]
+#[derive(PartialEq, Debug)]
+struct Config {
+    pub event: Event,
+    pub enabled: bool,
+}
+
+#[derive(serde::Serialize)]
+struct EventV1 {
+    id: u32,
+    name: &'static str,
+}
+
+#[derive(serde::Serialize)]
+struct Envelope<T> {
+    versions: std::collections::BTreeMap<&'static str, u32>,
+    value: T,
+}
+
+fn registry() -> MigrationRegistry {
+    let mut registry = MigrationRegistry::new();
+    registry.register::<Event>().register::<Config>();
+    registry
+}
+
+#[test]
+fn test_registry_keys() {
+    let registry = registry();
+    assert_eq!(registry.type_keys().collect::<Vec<_>>(), vec!["test_registry::Config", "test_registry::Event"]);
+    assert!(registry.contains("test_registry::Event"));
+    assert!(!registry.contains("Event"));
+
+    let err = registry.upgrade_dry_run::<Json>("test_registry::Missing", b"{}").unwrap_err();
+    assert_eq!(err.to_string(), "The type test_registry::Missing is not registered");
+}
+
+#[test]
+fn test_registry_upgrade_bytes() {
+    let registry = registry();
+    let expected = Event { id: 1, label: "a".to_string() };
+    let old = Envelope { versions: [("test_registry::Event", 1)].into(), value: EventV1 { id: 1, name: "a" } };
+
+    let mut output = Vec::new();
+    let report = registry.upgrade::<Json>("test_registry::Event", &serde_json::to_vec(&old).unwrap(), &mut output).unwrap();
+    assert!(report.changed);
+    assert_eq!(serde_json::from_slice::<Versioned<Event>>(&output).unwrap().0, expected);
+
+    let mut output = Vec::new();
+    registry.upgrade::<Bincode>("test_registry::Event", &bincode::serialize(&old).unwrap(), &mut output).unwrap();
+    assert_eq!(bincode::deserialize::<Versioned<Event>>(&output).unwrap().0, expected);
+
+    let report = registry.upgrade_dry_run::<Postcard>("test_registry::Event", &postcard::to_stdvec(&old).unwrap()).unwrap();
+    assert_eq!(report.migrations().collect::<Vec<_>>(), vec![("test_registry::Event", 1, 2)]);
+}
+
+/// A format which is not part of this crate.
+struct PrettyJson;
+
+impl Format for PrettyJson {
+    const NAME: &'static str = "pretty json";
+
+    fn to_bytes<T: serde::Serialize + ?Sized>(value: &T) -> Result<Vec<u8>, FormatError> {
+        serde_json::to_vec_pretty(value).map_err(FormatError::new)
+    }
+
+    fn from_bytes<T: serde::de::DeserializeOwned>(input: &[u8]) -> Result<T, FormatError> {
+        Json::from_bytes(input)
+    }
+
+    fn peek_versions(input: &[u8]) -> Result<VersionMap, FormatError> {
+        Json::peek_versions(input)
+    }
+}
+
+#[test]
+fn test_registry_custom_format() {
+    let mut registry = registry();
+    let old = Envelope { versions: [("test_registry::Event", 1)].into(), value: EventV1 { id: 1, name: "a" } };
+    let input = serde_json::to_vec(&old).unwrap();
+    let err = registry.upgrade_dry_run::<PrettyJson>("test_registry::Event", &input).unwrap_err();
+    assert_eq!(err.to_string(), "The format pretty json is not registered for the type test_registry::Event");
+
+    registry.register_format::<Event, PrettyJson>();
+    let mut output = Vec::new();
+    let report = registry.upgrade::<PrettyJson>("test_registry::Event", &input, &mut output).unwrap();
+    assert!(report.changed);
+    assert_eq!(output, serde_json::to_vec_pretty(&Versioned(Event { id: 1, label: "a".to_string() })).unwrap());
+    // The formats of this crate are still registered
+    assert!(registry.upgrade_dry_run::<Json>("test_registry::Event", &input).unwrap().changed);
+}
+
+#[test]
+fn test_registry_migrate_json() {
+    let registry = registry();
+    let value = serde_json::json!({
+        "versions": { "test_registry::Config": 1, "test_registry::Event": 1 },
+        "value": { "event": { "id": 1, "name": "a" }, "enabled": true },
+    });
+    let migrated = registry.migrate_json("test_registry::Config", value).unwrap();
+    assert_eq!(migrated, serde_json::json!({
+        "versions": { "test_registry::Config": 1, "test_registry::Event": 2 },
+        "value": { "event": { "id": 1, "label": "a" }, "enabled": true },
+    }));
+
+    let err = registry.migrate_json("test_registry::Event", serde_json::json!({
+        "versions": { "test_registry::Event": 9 },
+        "value": { "id": 1, "label": "a" },
+    })).unwrap_err();
+    assert_eq!(err.to_string(), "Invalid version for Event (got 9)");
+
+    let err = registry.migrate_json("test_registry::Event", serde_json::json!({ "value": {} })).unwrap_err();
+    assert_eq!(err.to_string(), "missing field `versions`");
+
+    // The same header forms as Versioned are accepted, including the legacy envelope
+    let migrated = registry.migrate_json("test_registry::Event", serde_json::json!({
+        "version": 1,
+        "value": { "id": 1, "name": "a" },
+    })).unwrap();
+    assert_eq!(migrated, serde_json::json!({
+        "versions": { "test_registry::Event": 2 },
+        "value": { "id": 1, "label": "a" },
+    }));
+}
@@ -0,0 +1,120 @@
+use serde_migrate::{versioned, SchemaFingerprints, SchemaLock, SchemaLockError};
+
+#[versioned]
+#[serde(rename_all = "camelCase")]
+struct Item {
+    pub item_id: u32,
+    #[version(end = 2)]
+    pub tags: Vec<String>,
+    #[version(start = 2)]
+    #[serde(default)]
+    pub labels: Vec<String>,
+}
+
+impl item_migrations::Migrate for Item {
+    fn to_v2(v: item_migrations::ItemV1) -> item_migrations::ItemV2 {
+        item_migrations::ItemV2 { item_id: v.item_id, labels: v.tags }
+    }
+}
+
+#[versioned(min_supported = 3)]
+struct Recent {
+    #[version(start = 2)]
+    pub a: u32,
+    #[version(start = 4)]
+    pub b: Option<Item>,
+}
+
+impl recent_migrations::Migrate for Recent {
+    fn to_v4(v: recent_migrations::RecentV3) -> recent_migrations::RecentV4 {
+        recent_migrations::RecentV4 { a: v.a, b: None }
+    }
+}
+
+#[versioned]
+struct Pair<A, B> {
+    pub first: A,
+    pub second: B,
+}
+
+#[test]
+fn test_fingerprints() {
+    assert_eq!(Item::FINGERPRINTS, &[
+        (1, r#"#[serde(rename_all = "camelCase")] { item_id: u32, tags: Vec<String> }"#),
+        (2, r#"#[serde(rename_all = "camelCase")] { item_id: u32, #[serde(default)] labels: Vec<String> }"#),
+    ]);
+    assert_eq!(Recent::FINGERPRINTS, &[
+        (3, "{ a: u32 }"),
+        (4, "{ a: u32, b: Option<Item> }"),
+    ]);
+}
+
+#[test]
+fn test_lock_file_roundtrip() {
+    let lock = SchemaLock::new().with::<Item>().with::<Recent>().with::<Pair<u32, Option<String>>>();
+    let parsed = SchemaLock::parse(&lock.to_string()).unwrap();
+    assert_eq!(parsed, lock);
+    assert_eq!(parsed.get("test_schema_lock::Recent", 4), Some("{ a: u32, b: Option<Item> }"));
+    // Type keys of generic types contain spaces
+    let pair = "test_schema_lock::Pair<u32, core::option::Option<alloc::string::String>>";
+    assert_eq!(parsed.get(pair, 1), Some("{ first: A, second: B }"));
+
+    assert!(matches!(SchemaLock::parse("a::B\tx\t{}"), Err(SchemaLockError::Parse { line: 1, .. })));
+    assert!(matches!(SchemaLock::parse("a::B 1 {}"), Err(SchemaLockError::Parse { line: 1, .. })));
+    assert!(matches!(SchemaLock::parse("# comment\na::B\t1\t{}\na::B\t1\t{}"), Err(SchemaLockError::Parse { line: 3, .. })));
+}
+
+#[test]
+fn test_check_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("schema.lock");
+    let lock = SchemaLock::new().with::<Item>();
+
+    // Checking never creates the file
+    let Err(SchemaLockError::Missing(missing)) = lock.check_file(&path) else {
+        panic!("expected the versions to be missing");
+    };
+    assert_eq!(missing, [("test_schema_lock::Item".to_owned(), 1), ("test_schema_lock::Item".to_owned(), 2)]);
+    assert!(!path.exists());
+
+    lock.update_file(&path).unwrap();
+    let contents = std::fs::read_to_string(&path).unwrap();
+    assert!(contents.contains("test_schema_lock::Item\t2\t"));
+    lock.check_file(&path).unwrap();
+
+    // A released version was edited
+    std::fs::write(&path, contents.replace("tags: Vec<String>", "tags: String")).unwrap();
+    let Err(SchemaLockError::Changed(changes)) = lock.check_file(&path) else {
+        panic!("expected the change to be detected");
+    };
+    assert_eq!(changes.len(), 1);
+    assert_eq!(changes[0].type_key, "test_schema_lock::Item");
+    assert_eq!(changes[0].version, 1);
+    assert!(changes[0].locked.contains("tags: String"));
+
+    lock.update_file(&path).unwrap();
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), contents);
+}
+
+#[test]
+fn test_new_versions() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("schema.lock");
+    let contents = "test_schema_lock::Item\t1\t#[serde(rename_all = \"camelCase\")] { item_id: u32, tags: Vec<String> }\nold::Type\t1\t{ x: u8 }\n";
+    std::fs::write(&path, contents).unwrap();
+
+    // A new version is an error until the lock file is updated, and the file is not written
+    let lock = SchemaLock::new().with::<Item>();
+    let Err(SchemaLockError::Missing(missing)) = lock.check_file(&path) else {
+        panic!("expected the new version to be missing");
+    };
+    assert_eq!(missing, [("test_schema_lock::Item".to_owned(), 2)]);
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), contents);
+
+    lock.update_file(&path).unwrap();
+    lock.check_file(&path).unwrap();
+    let updated = SchemaLock::parse(&std::fs::read_to_string(&path).unwrap()).unwrap();
+    assert!(updated.get("test_schema_lock::Item", 2).is_some());
+    // Entries of other types are kept
+    assert_eq!(updated.get("old::Type", 1), Some("{ x: u8 }"));
+}
@@ -0,0 +1,65 @@
+use serde_migrate::{versioned, VersionMap};
+
+#[versioned]
+#[derive(PartialEq, Debug)]
+struct Row {
+    pub id: u32,
+    #[version(start = 2)]
+    pub tags: Vec<Tag>,
+}
+
+#[versioned]
+#[derive(PartialEq, Debug)]
+struct Tag {
+    #[version(end = 2)]
+    pub label: String,
+    #[version(start = 2)]
+    pub name: String,
+}
+
+impl row_migrations::Migrate for Row {
+    fn to_v2(v: row_migrations::RowV1) -> row_migrations::RowV2 {
+        row_migrations::RowV2 {
+            id: v.id,
+            tags: vec![],
+        }
+    }
+}
+
+impl tag_migrations::Migrate for Tag {
+    fn to_v2(v: tag_migrations::TagV1) -> tag_migrations::TagV2 {
+        tag_migrations::TagV2 {
+            name: v.label,
+        }
+    }
+}
+
+#[test]
+fn test_sidecar_roundtrip() {
+    let orig = Row {
+        id: 1,
+        tags: vec![Tag { name: "a".to_string() }],
+    };
+
+    // The header is stored as json, and the payload as bincode
+    let mut payload = Vec::new();
+    let (versions, ()) = serde_migrate::serialize_with_versions(&orig, &mut bincode::Serializer::new(&mut payload, bincode::DefaultOptions::new())).unwrap();
+    assert_eq!(versions, serde_migrate::versions_of(&orig));
+    let header = serde_json::to_string(&versions).unwrap();
+    assert_eq!(header, r#"{"test_sidecar::Row":2,"test_sidecar::Tag":2}"#);
+
+    let versions: VersionMap = serde_json::from_str(&header).unwrap();
+    let decoded: Row = serde_migrate::deserialize_with_versions(&versions, &mut bincode::Deserializer::from_slice(&payload, bincode::DefaultOptions::new())).unwrap();
+    assert_eq!(decoded, orig);
+}
+
+#[test]
+fn test_sidecar_migration() {
+    let versions: VersionMap = [("test_sidecar::Row".to_string(), 2), ("test_sidecar::Tag".to_string(), 1)].into_iter().collect();
+    let payload = r#"{ "id": 1, "tags": [{ "label": "a" }] }"#;
+    let decoded: Row = serde_migrate::deserialize_with_versions(&versions, &mut serde_json::Deserializer::from_str(payload)).unwrap();
+    assert_eq!(decoded, Row {
+        id: 1,
+        tags: vec![Tag { name: "a".to_string() }],
+    });
+}
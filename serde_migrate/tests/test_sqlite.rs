@@ -0,0 +1,112 @@
+#![cfg(feature = "rusqlite")]
+
+use serde_migrate::{rusqlite::Connection, upgrade_sqlite_column, versioned, Bincode, Json, Versioned};
+
+#[versioned]
+#[derive(Debug, PartialEq)]
+struct Document {
+    #[version(end = 2)]
+    pub title: String,
+    #[version(start = 2)]
+    pub titles: Vec<String>,
+}
+
+impl document_migrations::Migrate for Document {
+    fn to_v2(v: document_migrations::DocumentV1) -> document_migrations::DocumentV2 {
+        document_migrations::DocumentV2 { titles: vec![v.title] }
+    }
+}
+
+fn v1_json(title: &str) -> String {
+    format!(r#"{{"versions":{{"{}":1}},"value":{{"title":"{}"}}}}"#, std::any::type_name::<Document>(), title)
+}
+
+fn open() -> Connection {
+    let conn = Connection::open_in_memory().unwrap();
+    conn.execute("CREATE TABLE documents (id INTEGER PRIMARY KEY, data)", ()).unwrap();
+    conn
+}
+
+#[test]
+fn test_to_sql_and_from_sql() {
+    let conn = open();
+    conn.execute("INSERT INTO documents (id, data) VALUES (1, ?1)", (Versioned(Document { titles: vec!["a".to_owned()] }),)).unwrap();
+    conn.execute("INSERT INTO documents (id, data) VALUES (2, ?1)", (v1_json("b"),)).unwrap();
+    conn.execute("INSERT INTO documents (id, data) VALUES (3, ?1)", (v1_json("c").into_bytes(),)).unwrap();
+
+    // Values are stored as json text
+    let text: String = conn.query_row("SELECT data FROM documents WHERE id = 1", (), |row| row.get(0)).unwrap();
+    assert!(text.contains(r#""titles":["a"]"#), "{}", text);
+
+    let get = |id: i64| conn.query_row("SELECT data FROM documents WHERE id = ?1", (id,), |row| row.get::<_, Versioned<Document>>(0)).unwrap().0;
+    assert_eq!(get(1), Document { titles: vec!["a".to_owned()] });
+    assert_eq!(get(2), Document { titles: vec!["b".to_owned()] });
+    assert_eq!(get(3), Document { titles: vec!["c".to_owned()] });
+
+    conn.execute("INSERT INTO documents (id, data) VALUES (4, 5)", ()).unwrap();
+    assert!(conn.query_row("SELECT data FROM documents WHERE id = 4", (), |row| row.get::<_, Versioned<Document>>(0)).is_err());
+}
+
+#[test]
+fn test_upgrade_column() {
+    let mut conn = open();
+    conn.execute("INSERT INTO documents (id, data) VALUES (1, ?1)", (v1_json("a"),)).unwrap();
+    conn.execute("INSERT INTO documents (id, data) VALUES (2, ?1)", (v1_json("b").into_bytes(),)).unwrap();
+    conn.execute("INSERT INTO documents (id, data) VALUES (3, ?1)", (Versioned(Document { titles: vec![] }),)).unwrap();
+    conn.execute("INSERT INTO documents (id, data) VALUES (4, 'not json')", ()).unwrap();
+    conn.execute("INSERT INTO documents (id, data) VALUES (5, NULL)", ()).unwrap();
+
+    let report = upgrade_sqlite_column::<Document, Json>(&mut conn, "documents", "data").unwrap();
+    assert_eq!(report.records, 4);
+    assert_eq!(report.upgraded, 2);
+    assert_eq!(report.errors.len(), 1);
+    assert_eq!(report.errors[0].0, 4);
+
+    // Text stays text, and blobs stay blobs
+    let types: Vec<String> = conn
+        .prepare("SELECT typeof(data) FROM documents ORDER BY id").unwrap()
+        .query_map((), |row| row.get(0)).unwrap()
+        .collect::<Result<_, _>>().unwrap();
+    assert_eq!(types, ["text", "blob", "text", "text", "null"]);
+    let expected = serde_json::to_vec(&Versioned(Document { titles: vec!["b".to_owned()] })).unwrap();
+    let blob: Vec<u8> = conn.query_row("SELECT data FROM documents WHERE id = 2", (), |row| row.get(0)).unwrap();
+    assert_eq!(blob, expected);
+    let invalid: String = conn.query_row("SELECT data FROM documents WHERE id = 4", (), |row| row.get(0)).unwrap();
+    assert_eq!(invalid, "not json");
+
+    let report = upgrade_sqlite_column::<Document, Json>(&mut conn, "documents", "data").unwrap();
+    assert_eq!(report.upgraded, 0);
+}
+
+#[test]
+fn test_upgrade_binary_column() {
+    let mut conn = Connection::open_in_memory().unwrap();
+    conn.execute(r#"CREATE TABLE "odd ""name""" ("the data" BLOB)"#, ()).unwrap();
+    let v1 = bincode::serialize(&(vec![(std::any::type_name::<Document>(), 1u32)], "a")).unwrap();
+    conn.execute(r#"INSERT INTO "odd ""name""" VALUES (?1)"#, (v1,)).unwrap();
+
+    let report = upgrade_sqlite_column::<Document, Bincode>(&mut conn, "odd \"name\"", "the data").unwrap();
+    assert_eq!(report.upgraded, 1);
+    let stored: Vec<u8> = conn.query_row(r#"SELECT "the data" FROM "odd ""name""""#, (), |row| row.get(0)).unwrap();
+    assert_eq!(stored, bincode::serialize(&Versioned(Document { titles: vec!["a".to_owned()] })).unwrap());
+}
+
+#[test]
+fn test_upgrade_column_in_batches() {
+    let mut conn = open();
+    {
+        let txn = conn.transaction().unwrap();
+        for id in 0..2500 {
+            let data = if id % 2 == 0 { Some(v1_json(&id.to_string())) } else { None };
+            txn.execute("INSERT INTO documents (id, data) VALUES (?1, ?2)", (id * 3, data)).unwrap();
+        }
+        txn.commit().unwrap();
+    }
+
+    let report = upgrade_sqlite_column::<Document, Json>(&mut conn, "documents", "data").unwrap();
+    assert_eq!(report.records, 1250);
+    assert_eq!(report.upgraded, 1250);
+    let last = conn.query_row("SELECT data FROM documents WHERE id = 7494", (), |row| row.get::<_, Versioned<Document>>(0)).unwrap().0;
+    assert_eq!(last, Document { titles: vec!["2498".to_owned()] });
+    assert_eq!(upgrade_sqlite_column::<Document, Json>(&mut conn, "documents", "data").unwrap().upgraded, 0);
+}
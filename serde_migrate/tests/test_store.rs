@@ -0,0 +1,104 @@
+#![cfg(feature = "redb")]
+
+use serde_migrate::{redb::{Database, TableDefinition}, versioned, Bincode, Json, Versioned, VersionedTable};
+
+#[versioned]
+#[derive(Debug, PartialEq)]
+struct Settings {
+    #[version(end = 2)]
+    pub volume: u8,
+    #[version(start = 2)]
+    pub volume_percent: f32,
+}
+
+impl settings_migrations::Migrate for Settings {
+    fn to_v2(v: settings_migrations::SettingsV1) -> settings_migrations::SettingsV2 {
+        settings_migrations::SettingsV2 { volume_percent: v.volume as f32 / 255.0 * 100.0 }
+    }
+}
+
+const TABLE: TableDefinition<&[u8], &[u8]> = TableDefinition::new("settings");
+
+fn v1_json(volume: u8) -> String {
+    format!(r#"{{"versions":{{"{}":1}},"value":{{"volume":{}}}}}"#, std::any::type_name::<Settings>(), volume)
+}
+
+/// Writes raw bytes to the table, like an old version of the program would have.
+fn write_raw(db: &Database, key: &str, bytes: &[u8]) {
+    let txn = db.begin_write().unwrap();
+    txn.open_table(TABLE).unwrap().insert(key.as_bytes(), bytes).unwrap();
+    txn.commit().unwrap();
+}
+
+fn read_raw(db: &Database, key: &str) -> Vec<u8> {
+    let txn = db.begin_read().unwrap();
+    let table = txn.open_table(TABLE).unwrap();
+    let value = table.get(key.as_bytes()).unwrap().unwrap().value().to_vec();
+    value
+}
+
+#[test]
+fn test_migrate_on_read() {
+    let dir = tempfile::tempdir().unwrap();
+    let db = Database::create(dir.path().join("db.redb")).unwrap();
+    let table = VersionedTable::<Settings, Json>::new(&db, "settings");
+    assert_eq!(table.get("missing").unwrap(), None);
+
+    write_raw(&db, "user", v1_json(255).as_bytes());
+    assert_eq!(table.get("user").unwrap(), Some(Settings { volume_percent: 100.0 }));
+
+    // The upgraded value was written back at the latest version, so the migration does not run again
+    let upgraded = read_raw(&db, "user");
+    assert_eq!(upgraded, serde_json::to_vec(&Versioned(Settings { volume_percent: 100.0 })).unwrap());
+    assert_eq!(table.get("user").unwrap(), Some(Settings { volume_percent: 100.0 }));
+    assert_eq!(read_raw(&db, "user"), upgraded);
+
+    table.remove("user").unwrap();
+    assert_eq!(table.get("user").unwrap(), None);
+}
+
+#[test]
+fn test_insert_and_get() {
+    let dir = tempfile::tempdir().unwrap();
+    let db = Database::create(dir.path().join("db.redb")).unwrap();
+    let table = VersionedTable::<Settings, Bincode>::new(&db, "settings");
+    table.insert("a", &Settings { volume_percent: 50.0 }).unwrap();
+    let stored = read_raw(&db, "a");
+    assert_eq!(table.get("a").unwrap(), Some(Settings { volume_percent: 50.0 }));
+    // Values at the latest version are not written again
+    assert_eq!(read_raw(&db, "a"), stored);
+}
+
+#[test]
+fn test_invalid_value() {
+    let dir = tempfile::tempdir().unwrap();
+    let db = Database::create(dir.path().join("db.redb")).unwrap();
+    let table = VersionedTable::<Settings, Json>::new(&db, "settings");
+    write_raw(&db, "broken", b"{");
+    assert!(table.get("broken").is_err());
+    assert_eq!(read_raw(&db, "broken"), b"{");
+}
+
+#[test]
+fn test_upgrade_all() {
+    let dir = tempfile::tempdir().unwrap();
+    let db = Database::create(dir.path().join("db.redb")).unwrap();
+    let table = VersionedTable::<Settings, Json>::new(&db, "settings");
+    for i in 0..7u8 {
+        write_raw(&db, &format!("old{}", i), v1_json(i).as_bytes());
+    }
+    table.insert("new", &Settings { volume_percent: 1.0 }).unwrap();
+    write_raw(&db, "broken", b"not json");
+
+    let report = table.upgrade_all(3).unwrap();
+    assert_eq!(report.records, 9);
+    assert_eq!(report.upgraded, 7);
+    assert_eq!(report.errors.len(), 1);
+    assert_eq!(report.errors[0].0, b"broken");
+    assert_eq!(read_raw(&db, "broken"), b"not json");
+    assert_eq!(read_raw(&db, "old0"), serde_json::to_vec(&Versioned(Settings { volume_percent: 0.0 })).unwrap());
+
+    let report = table.upgrade_all(3).unwrap();
+    assert_eq!(report.records, 9);
+    assert_eq!(report.upgraded, 0);
+}
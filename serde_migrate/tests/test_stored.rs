@@ -0,0 +1,105 @@
+use serde_migrate::{versioned, Versioned, Stored};
+
+#[versioned]
+#[derive(PartialEq, Debug)]
+struct Awrap {
+    #[version(end = 2)]
+    pub a: Vec<Stored<A>>,
+    #[version(start = 2)]
+    pub a: Vec<A>,
+    #[version(start = 2)]
+    pub b_total: u32,
+}
+
+#[versioned]
+#[derive(PartialEq, Debug)]
+struct A {
+    pub a: u32,
+    #[version(end = 2)]
+    pub b: u32,
+}
+
+impl a_migrations::Migrate for A {
+    fn to_v2(v: a_migrations::AV1) -> a_migrations::AV2 {
+        a_migrations::AV2 {
+            a: v.a,
+        }
+    }
+}
+
+impl awrap_migrations::Migrate for Awrap {
+    fn to_v2(v: awrap_migrations::AwrapV1) -> awrap_migrations::AwrapV2 {
+        // The field `b` is moved from A to Awrap, so we need to see A as it was stored
+        let b_total = v.a.iter().map(|a| match a.get() {
+            a_migrations::DataVersions::V1(a) => a.b,
+            a_migrations::DataVersions::V2(_) => 0,
+        }).sum();
+        awrap_migrations::AwrapV2 {
+            a: v.a.into_iter().map(Stored::migrate).collect(),
+            b_total,
+        }
+    }
+}
+
+#[test]
+fn test_stored() {
+    let expected = Awrap {
+        a: vec![A { a: 1 }, A { a: 2 }],
+        b_total: 30,
+    };
+
+    let json = r#"{ "versions": { "test_stored::Awrap": 1, "test_stored::A": 1 }, "value": { "a": [{ "a": 1, "b": 10 }, { "a": 2, "b": 20 }] } }"#;
+    let decoded = serde_json::from_str::<Versioned<Awrap>>(json).unwrap().0;
+    assert_eq!(decoded, expected);
+
+    // The inner type may already have been migrated, even if the outer type has not
+    let json = r#"{ "versions": { "test_stored::Awrap": 1, "test_stored::A": 2 }, "value": { "a": [{ "a": 1 }, { "a": 2 }] } }"#;
+    let decoded = serde_json::from_str::<Versioned<Awrap>>(json).unwrap().0;
+    assert_eq!(decoded, Awrap {
+        b_total: 0,
+        ..expected
+    });
+
+    #[derive(serde::Serialize)]
+    struct AwrapV1 {
+        a: Vec<AV1>,
+    }
+    #[derive(serde::Serialize)]
+    struct AV1 {
+        a: u32,
+        b: u32,
+    }
+    let bc = bincode::serialize(&(vec![("test_stored::Awrap", 1u32), ("test_stored::A", 1)], AwrapV1 { a: vec![AV1 { a: 1, b: 10 }, AV1 { a: 2, b: 20 }] })).unwrap();
+    let decoded = bincode::deserialize::<Versioned<Awrap>>(&bc).unwrap().0;
+    assert_eq!(decoded, Awrap {
+        a: vec![A { a: 1 }, A { a: 2 }],
+        b_total: 30,
+    });
+}
+
+mod public {
+    use serde_migrate::versioned;
+
+    #[versioned]
+    #[derive(PartialEq, Debug)]
+    pub struct Public {
+        #[version(end = 2)]
+        pub a: u32,
+        #[version(start = 2)]
+        pub b: u32,
+    }
+
+    impl public_migrations::Migrate for Public {
+        fn to_v2(v: public_migrations::PublicV1) -> public_migrations::PublicV2 {
+            public_migrations::PublicV2 { b: v.a }
+        }
+    }
+}
+
+#[test]
+fn test_public_type() {
+    // Public types must not expose crate-private types through MigrateStored
+    let json = r#"{ "versions": { "test_stored::public::Public": 1 }, "value": { "a": 1 } }"#;
+    let decoded = serde_json::from_str::<Versioned<public::Public>>(json).unwrap().0;
+    assert_eq!(decoded, public::Public { b: 1 });
+}
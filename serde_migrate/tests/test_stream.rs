@@ -0,0 +1,102 @@
+use serde_migrate::{versioned, Format, Json, Postcard, RecordReader, Versioned};
+
+#[versioned]
+#[derive(PartialEq, Debug, Clone)]
+struct Event {
+    pub id: u32,
+    #[version(end = 2)]
+    pub kind: String,
+    #[version(start = 2)]
+    pub kind: Kind,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, PartialEq, Debug, Clone)]
+enum Kind {
+    Click,
+    Other(String),
+}
+
+impl event_migrations::Migrate for Event {
+    fn to_v2(v: event_migrations::EventV1) -> event_migrations::EventV2 {
+        event_migrations::EventV2 {
+            id: v.id,
+            kind: if v.kind == "click" { Kind::Click } else { Kind::Other(v.kind) },
+        }
+    }
+}
+
+#[derive(serde::Serialize)]
+struct EventV1 {
+    id: u32,
+    kind: &'static str,
+}
+
+fn old_record<F: Format>(id: u32, kind: &'static str) -> Vec<u8> {
+    // Pretend the record was written when Event was at version 1
+    let mut versions = std::collections::BTreeMap::new();
+    versions.insert("test_stream::Event", 1u32);
+    #[derive(serde::Serialize)]
+    struct Envelope<V> {
+        versions: std::collections::BTreeMap<&'static str, u32>,
+        value: V,
+    }
+    F::to_bytes(&Envelope { versions, value: EventV1 { id, kind } }).unwrap()
+}
+
+fn check_format<F: Format>(corrupt: &[u8]) {
+    let mut input = Vec::new();
+    F::write_record(&mut input, &old_record::<F>(1, "click")).unwrap();
+    F::write_record(&mut input, corrupt).unwrap();
+    F::write_record(&mut input, &F::to_bytes(&Versioned(&Event { id: 3, kind: Kind::Click })).unwrap()).unwrap();
+    F::write_record(&mut input, &old_record::<F>(4, "scroll")).unwrap();
+
+    let records: Vec<_> = RecordReader::<_, Event, F>::new(&input[..]).collect();
+    assert_eq!(records.len(), 4, "{}", F::NAME);
+    assert_eq!(records[0].as_ref().unwrap(), &Event { id: 1, kind: Kind::Click });
+    assert_eq!(records[1].as_ref().unwrap_err().index, 1);
+    assert_eq!(records[2].as_ref().unwrap(), &Event { id: 3, kind: Kind::Click });
+    assert_eq!(records[3].as_ref().unwrap(), &Event { id: 4, kind: Kind::Other("scroll".to_string()) });
+
+    let mut output = Vec::new();
+    let report = serde_migrate::upgrade_records::<Event, F>(&input[..], &mut output).unwrap();
+    assert_eq!(report.records, 4);
+    assert_eq!(report.changed, 2);
+    assert_eq!(report.errors.len(), 1);
+    assert_eq!(report.errors[0].index, 1);
+
+    // The corrupt record is kept, and the rest are upgraded
+    let mut raw = Vec::new();
+    let mut reader = std::io::BufReader::new(&output[..]);
+    F::read_record(&mut reader, &mut raw).unwrap();
+    assert_eq!(raw, F::to_bytes(&Versioned(&Event { id: 1, kind: Kind::Click })).unwrap());
+    F::read_record(&mut reader, &mut raw).unwrap();
+    assert_eq!(raw, corrupt);
+
+    let mut output2 = Vec::new();
+    let report = serde_migrate::upgrade_records::<Event, F>(&output[..], &mut output2).unwrap();
+    assert_eq!(report.changed, 0);
+    assert_eq!(output, output2);
+}
+
+#[test]
+fn test_stream_json() {
+    check_format::<Json>(b"{ not json");
+}
+
+#[test]
+fn test_stream_postcard() {
+    check_format::<Postcard>(&[1, 2, 3]);
+}
+
+#[test]
+fn test_stream_truncated() {
+    let mut input = Vec::new();
+    Postcard::write_record(&mut input, &Postcard::to_bytes(&Versioned(&Event { id: 1, kind: Kind::Click })).unwrap()).unwrap();
+    Postcard::write_record(&mut input, &Postcard::to_bytes(&Versioned(&Event { id: 2, kind: Kind::Click })).unwrap()).unwrap();
+    input.pop();
+
+    let records: Vec<_> = RecordReader::<_, Event, Postcard>::new(&input[..]).collect();
+    assert_eq!(records.len(), 2);
+    assert!(records[0].is_ok());
+    assert!(records[1].as_ref().unwrap_err().error.to_string().contains("truncated"));
+}
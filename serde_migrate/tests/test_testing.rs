@@ -0,0 +1,109 @@
+#![cfg(feature = "testing")]
+
+use serde_migrate::{testing::{assert_migrates, check_migrations}, versioned};
+
+mod v1 {
+    use serde_migrate::versioned;
+
+    #[versioned]
+    pub struct Item {
+        pub name: String,
+    }
+}
+
+mod v2 {
+    use serde_migrate::versioned;
+
+    #[versioned]
+    pub struct Item {
+        #[version(end = 2)]
+        pub name: String,
+        #[version(start = 2)]
+        pub names: Vec<String>,
+    }
+
+    impl item_migrations::Migrate for Item {
+        fn to_v2(v: item_migrations::ItemV1) -> item_migrations::ItemV2 {
+            item_migrations::ItemV2 { names: vec![v.name] }
+        }
+    }
+}
+
+#[versioned]
+struct Unrelated {
+    pub a: u32,
+}
+
+/// Writes the fixtures of v1 with a type that only has one version, and renames them for the type with two versions.
+fn bless_v1(dir: &std::path::Path) {
+    check_migrations(dir, &v1::Item { name: "a".to_string() }, true).unwrap();
+    for file in ["v1.json", "v1.bincode", "v1.postcard"] {
+        let bytes = std::fs::read(dir.join(file)).unwrap();
+        // Both types are named Item, but in different modules
+        let bytes = replace(&bytes, b"test_testing::v1::Item", b"test_testing::v2::Item");
+        std::fs::write(dir.join(file), bytes).unwrap();
+    }
+}
+
+fn replace(bytes: &[u8], from: &[u8], to: &[u8]) -> Vec<u8> {
+    assert_eq!(from.len(), to.len());
+    let mut bytes = bytes.to_vec();
+    let i = bytes.windows(from.len()).position(|w| w == from).unwrap();
+    bytes[i..i + from.len()].copy_from_slice(to);
+    bytes
+}
+
+#[test]
+fn test_missing_fixtures() {
+    let tmp = tempfile::tempdir().unwrap();
+    let dir = tmp.path().join("fixtures");
+    let problems = check_migrations(&dir, &Unrelated { a: 1 }, false).unwrap_err();
+    assert_eq!(problems.len(), 3, "{:?}", problems);
+    assert!(problems[0].ends_with("v1.json: Missing fixture for the current version"), "{:?}", problems);
+}
+
+#[test]
+fn test_bless_and_check() {
+    let tmp = tempfile::tempdir().unwrap();
+    let dir = tmp.path().join("fixtures");
+    let sample = Unrelated { a: 1 };
+    check_migrations(&dir, &sample, true).unwrap();
+    for file in ["v1.json", "v1.bincode", "v1.postcard", "v1.expected.json"] {
+        assert!(dir.join(file).exists(), "{}", file);
+    }
+    assert_eq!(
+        std::fs::read_to_string(dir.join("v1.expected.json")).unwrap(),
+        "{\n  \"versions\": {\n    \"test_testing::Unrelated\": 1\n  },\n  \"value\": {\n    \"a\": 1\n  }\n}\n",
+    );
+    assert_migrates(&dir, &sample);
+}
+
+#[test]
+fn test_new_version() {
+    let tmp = tempfile::tempdir().unwrap();
+    let dir = tmp.path().join("fixtures");
+    bless_v1(&dir);
+    let sample = v2::Item { names: vec!["b".to_string(), "c".to_string()] };
+
+    // The expected value of v1 is still at version 1, and there are no fixtures for v2 yet
+    let problems = check_migrations(&dir, &sample, false).unwrap_err();
+    assert!(problems.iter().any(|p| p.contains("differs from v1.expected.json")), "{:?}", problems);
+    assert!(problems.iter().any(|p| p.ends_with("v2.json: Missing fixture for the current version")), "{:?}", problems);
+
+    check_migrations(&dir, &sample, true).unwrap();
+    let expected: serde_json::Value = serde_json::from_slice(&std::fs::read(dir.join("v1.expected.json")).unwrap()).unwrap();
+    assert_eq!(expected["value"], serde_json::json!({ "names": ["a"] }));
+    assert!(dir.join("v2.postcard").exists());
+    assert_migrates(&dir, &sample);
+}
+
+#[test]
+#[should_panic(expected = "v1.bincode")]
+fn test_corrupt_fixture() {
+    let tmp = tempfile::tempdir().unwrap();
+    let dir = tmp.path().join("fixtures");
+    let sample = Unrelated { a: 1 };
+    check_migrations(&dir, &sample, true).unwrap();
+    std::fs::write(dir.join("v1.bincode"), [1, 2, 3]).unwrap();
+    assert_migrates(&dir, &sample);
+}
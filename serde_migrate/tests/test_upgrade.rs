@@ -0,0 +1,102 @@
+use serde_migrate::{versioned, Bincode, Format, Json, Postcard, Versioned};
+
+#[versioned]
+#[derive(PartialEq, Debug)]
+struct Config {
+    pub name: String,
+    #[version(end = 2)]
+    pub retries: u32,
+    #[version(start = 2)]
+    pub max_retries: u32,
+    pub limits: Limits,
+}
+
+#[versioned]
+#[derive(PartialEq, Debug)]
+struct Limits {
+    pub memory: u64,
+}
+
+impl config_migrations::Migrate for Config {
+    fn to_v2(v: config_migrations::ConfigV1) -> config_migrations::ConfigV2 {
+        config_migrations::ConfigV2 {
+            name: v.name,
+            max_retries: v.retries + 1,
+            limits: v.limits,
+        }
+    }
+}
+
+#[derive(serde::Serialize)]
+struct ConfigV1 {
+    name: String,
+    retries: u32,
+    limits: Limits,
+}
+
+fn check_format<F: Format>() {
+    let old = Versioned(ConfigV1 {
+        name: "x".to_string(),
+        retries: 2,
+        limits: Limits { memory: 10 },
+    });
+    // Serializing the old struct records Limits, but not the unversioned ConfigV1
+    let input = F::to_bytes(&old).unwrap();
+
+    let report = serde_migrate::upgrade_dry_run::<Config, F>(&input).unwrap();
+    assert!(report.changed, "{}", F::NAME);
+    assert_eq!(report.migrations().collect::<Vec<_>>(), vec![("test_upgrade::Config", 1, 2)], "{}", F::NAME);
+
+    let mut output = Vec::new();
+    let report2 = serde_migrate::upgrade::<Config, F>(&input, &mut output).unwrap();
+    assert_eq!(report, report2);
+    let upgraded = F::from_bytes::<Versioned<Config>>(&output).unwrap().0;
+    assert_eq!(upgraded, Config {
+        name: "x".to_string(),
+        max_retries: 3,
+        limits: Limits { memory: 10 },
+    });
+
+    // Upgrading again does not change anything
+    let mut output2 = Vec::new();
+    let report = serde_migrate::upgrade::<Config, F>(&output, &mut output2).unwrap();
+    assert!(!report.changed, "{}", F::NAME);
+    assert_eq!(report.migrations().count(), 0);
+    assert_eq!(output, output2);
+}
+
+#[test]
+fn test_upgrade() {
+    check_format::<Json>();
+    check_format::<Bincode>();
+    check_format::<Postcard>();
+}
+
+#[test]
+fn test_upgrade_invalid() {
+    let mut output = Vec::new();
+    assert!(serde_migrate::upgrade::<Config, Json>(b"{}", &mut output).is_err());
+    assert!(output.is_empty());
+}
+
+#[test]
+fn test_upgrade_compares_headers() {
+    // Formatting differences do not need an upgrade
+    let input = br#"{ "versions": { "test_upgrade::Limits": 1 }, "value": { "memory": 1 } }"#;
+    let report = serde_migrate::upgrade_dry_run::<Limits, Json>(input).unwrap();
+    assert!(!report.changed);
+    assert_eq!(report.stored_versions, Some(report.latest_versions.clone()));
+}
+
+#[test]
+fn test_upgrade_legacy_envelope() {
+    let input = br#"{"version":1,"value":{"name":"x","retries":2,"limits":{"memory":10}}}"#;
+    let report = serde_migrate::upgrade_dry_run::<Config, Json>(input).unwrap();
+    // The stored versions are unknown, but the data is always written in the current format
+    assert!(report.changed);
+    assert_eq!(report.stored_versions, None);
+    assert_eq!(report.migrations().count(), 0);
+
+    let input = br#"{"version":2,"value":{"name":"x","max_retries":3,"limits":{"memory":10}}}"#;
+    assert!(serde_migrate::upgrade_dry_run::<Config, Json>(input).unwrap().changed);
+}
@@ -0,0 +1,86 @@
+use std::collections::HashMap;
+
+use serde_migrate::{versioned, FieldInfo, VersionedType};
+
+#[versioned]
+struct Simple {
+    pub a: u32,
+}
+
+#[versioned(min_supported = 2)]
+struct Item {
+    #[version(start = 1)]
+    pub id: u64,
+    #[version(start = 2, end = 4)]
+    pub names: HashMap<String, Vec<(u8, String)>>,
+    #[version(start = 3)]
+    pub data: Option<[u8; 4]>,
+    #[version(start = 4)]
+    #[serde(rename = "other")]
+    pub names: Vec<std::string::String>,
+}
+
+impl item_migrations::Migrate for Item {
+    fn to_v3(v: item_migrations::ItemV2) -> item_migrations::ItemV3 {
+        item_migrations::ItemV3 { id: v.id, names: v.names, data: None }
+    }
+
+    fn to_v4(v: item_migrations::ItemV3) -> item_migrations::ItemV4 {
+        item_migrations::ItemV4 { id: v.id, data: v.data, names: v.names.into_keys().collect() }
+    }
+}
+
+#[versioned]
+struct Unusual {
+    pub single: (u8,),
+    pub absolute: ::std::vec::Vec<::std::primitive::u8>,
+    pub nested: Option<Box<[[bool; 2]]>>,
+    pub unit: (),
+}
+
+#[versioned]
+struct Generic<T> {
+    pub value: T,
+}
+
+#[test]
+fn test_simple() {
+    assert_eq!(Simple::CURRENT_VERSION, 1);
+    assert_eq!(Simple::MIN_SUPPORTED_VERSION, 1);
+    assert_eq!(Simple::type_key(), "test_versioned_type::Simple");
+    let history = Simple::history();
+    assert_eq!(history.len(), 1);
+    assert_eq!(history[0].version, 1);
+    assert_eq!(history[0].fields, &[FieldInfo { name: "a", ty: "u32", start: 1, end: None }]);
+}
+
+#[test]
+fn test_history() {
+    assert_eq!(Item::CURRENT_VERSION, 4);
+    assert_eq!(Item::MIN_SUPPORTED_VERSION, 2);
+
+    let history = Item::history();
+    assert_eq!(history.iter().map(|v| v.version).collect::<Vec<_>>(), vec![2, 3, 4]);
+
+    let id = FieldInfo { name: "id", ty: "u64", start: 2, end: None };
+    let old_names = FieldInfo { name: "names", ty: "HashMap<String, Vec<(u8, String)>>", start: 2, end: Some(4) };
+    let data = FieldInfo { name: "data", ty: "Option<[u8; 4]>", start: 3, end: None };
+    let new_names = FieldInfo { name: "names", ty: "Vec<std::string::String>", start: 4, end: None };
+    assert_eq!(history[0].fields, &[id, old_names]);
+    assert_eq!(history[1].fields, &[id, old_names, data]);
+    assert_eq!(history[2].fields, &[id, data, new_names]);
+    assert_eq!(history[2].field("names"), Some(&new_names));
+    assert_eq!(history[2].field("other"), None);
+}
+
+#[test]
+fn test_generic() {
+    assert_eq!(Generic::<u32>::type_key(), "test_versioned_type::Generic<u32>");
+    assert_eq!(Generic::<u32>::history()[0].fields[0].ty, "T");
+}
+
+#[test]
+fn test_type_strings() {
+    let types = Unusual::history()[0].fields.iter().map(|f| f.ty).collect::<Vec<_>>();
+    assert_eq!(types, ["(u8,)", "::std::vec::Vec<::std::primitive::u8>", "Option<Box<[[bool; 2]]>>", "()"]);
+}
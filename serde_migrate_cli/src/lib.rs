@@ -0,0 +1,383 @@
+//! The `serde-migrate` command line tool, for inspecting and upgrading files containing data serialized with [`serde_migrate::Versioned`].
+//!
+//! ```text
+//! serde-migrate header [--format <format>] <path>...
+//! serde-migrate histogram [--format <format>] <path>...
+//! serde-migrate validate [--format <format>] [--type <type>] <path>...
+//! serde-migrate upgrade [--format <format>] [--type <type>] [--dry-run] <path>...
+//! ```
+//!
+//! Directories are searched recursively. The format is detected from the file extension (`.json`, `.bincode` or `.postcard`), unless `--format` is given.
+//!
+//! The `serde-migrate` binary can only read version headers, since it does not know your types.
+//! To validate file contents and upgrade files, build your own binary with your types registered:
+//!
+//! ```rust,no_run
+//! # use serde_migrate::versioned;
+//! #[versioned]
+//! struct Event {
+//!     pub id: u32,
+//! }
+//!
+//! fn main() {
+//!     serde_migrate_cli::Cli::new()
+//!         .register::<Event>()
+//!         .run()
+//! }
+//! ```
+
+use std::{collections::BTreeMap, fs, io::Write, path::{Path, PathBuf}};
+
+use serde::{Serialize, de::DeserializeOwned};
+use serde_migrate::{Bincode, Format, FormatError, Json, MigrationRegistry, Postcard, UpgradeReport, VersionMap};
+
+const USAGE: &str = "Usage:
+    serde-migrate header [--format <format>] <path>...
+    serde-migrate histogram [--format <format>] <path>...
+    serde-migrate validate [--format <format>] [--type <type>] <path>...
+    serde-migrate upgrade [--format <format>] [--type <type>] [--dry-run] <path>...
+
+Formats: json, bincode, postcard. By default, the format is detected from the file extension.";
+
+/// A serialization format supported by the command line tool.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileFormat {
+    Json,
+    Bincode,
+    Postcard,
+}
+
+impl FileFormat {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "json" => Some(FileFormat::Json),
+            "bincode" => Some(FileFormat::Bincode),
+            "postcard" => Some(FileFormat::Postcard),
+            _ => None,
+        }
+    }
+
+    /// Detects the format from the extension of a file.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        FileFormat::from_name(path.extension()?.to_str()?)
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            FileFormat::Json => Json::NAME,
+            FileFormat::Bincode => Bincode::NAME,
+            FileFormat::Postcard => Postcard::NAME,
+        }
+    }
+
+    fn peek_versions(self, input: &[u8]) -> Result<VersionMap, FormatError> {
+        match self {
+            FileFormat::Json => Json::peek_versions(input),
+            FileFormat::Bincode => Bincode::peek_versions(input),
+            FileFormat::Postcard => Postcard::peek_versions(input),
+        }
+    }
+
+    fn upgrade(self, registry: &MigrationRegistry, type_key: &str, input: &[u8]) -> Result<(UpgradeReport, Vec<u8>), FormatError> {
+        let mut output = Vec::new();
+        let report = match self {
+            FileFormat::Json => registry.upgrade::<Json>(type_key, input, &mut output)?,
+            FileFormat::Bincode => registry.upgrade::<Bincode>(type_key, input, &mut output)?,
+            FileFormat::Postcard => registry.upgrade::<Postcard>(type_key, input, &mut output)?,
+        };
+        Ok((report, output))
+    }
+}
+
+/// The command line tool, with the types it can validate and upgrade.
+#[derive(Default)]
+pub struct Cli {
+    registry: MigrationRegistry,
+}
+
+struct Options {
+    format: Option<FileFormat>,
+    type_name: Option<String>,
+    dry_run: bool,
+    paths: Vec<PathBuf>,
+}
+
+impl Cli {
+    pub fn new() -> Self {
+        Cli::default()
+    }
+
+    /// Registers a type, so that files can be validated and upgraded as that type.
+    ///
+    /// Register every versioned type which may be stored in the files, including nested types. Otherwise `validate` reports them as unknown.
+    pub fn register<T: DeserializeOwned + Serialize>(mut self) -> Self {
+        self.registry.register::<T>();
+        self
+    }
+
+    /// Uses the types registered in an existing registry.
+    pub fn with_registry(registry: MigrationRegistry) -> Self {
+        Cli { registry }
+    }
+
+    /// Runs the tool with the arguments of the process, and exits the process.
+    ///
+    /// Exits with status 1 if any file could not be read, validated or upgraded, and with status 2 if the arguments are invalid.
+    pub fn run(&self) -> ! {
+        let args: Vec<String> = std::env::args().skip(1).collect();
+        match self.run_with_args(&args, &mut std::io::stdout().lock()) {
+            Ok(true) => std::process::exit(0),
+            Ok(false) => std::process::exit(1),
+            Err(e) => {
+                eprintln!("error: {}\n\n{}", e, USAGE);
+                std::process::exit(2)
+            }
+        }
+    }
+
+    /// Runs the tool with the given arguments, not including the program name, and writes the output to `out`.
+    ///
+    /// Returns `Ok(false)` if any file could not be read, validated or upgraded.
+    pub fn run_with_args(&self, args: &[String], out: &mut dyn Write) -> Result<bool, String> {
+        let Some((command, rest)) = args.split_first() else {
+            return Err("Missing command".to_string());
+        };
+        if !["header", "histogram", "validate", "upgrade"].contains(&command.as_str()) {
+            return Err(format!("Unknown command '{}'", command));
+        }
+        let options = parse_options(rest)?;
+        let files = collect_files(&options)?;
+        match command.as_str() {
+            "header" => header(&files, out),
+            "histogram" => histogram(&files, out),
+            "validate" => self.validate(&options, &files, out),
+            _ => self.upgrade(&options, &files, out),
+        }
+        .map_err(|e| e.to_string())
+    }
+
+    /// Finds the type that files should be deserialized as. Returns `None` if no type was given and it is ambiguous.
+    fn root_type(&self, options: &Options) -> Result<Option<&'static str>, String> {
+        let Some(name) = &options.type_name else {
+            let mut keys = self.registry.type_keys();
+            return Ok(match (keys.next(), keys.next()) {
+                (Some(key), None) => Some(key),
+                _ => None,
+            });
+        };
+        let mut matching = self.registry.type_keys().filter(|key| key == name || key.rsplit("::").next() == Some(name.as_str()));
+        match (matching.next(), matching.next()) {
+            (Some(t), None) => Ok(Some(t)),
+            (Some(_), Some(_)) => Err(format!("The type '{}' is ambiguous. Use the full type name", name)),
+            (None, _) => Err(format!("Unknown type '{}'. Registered types: {}", name, self.type_names())),
+        }
+    }
+
+    fn type_names(&self) -> String {
+        let names: Vec<_> = self.registry.type_keys().collect();
+        if names.is_empty() {
+            return "none".to_string();
+        }
+        names.join(", ")
+    }
+
+    fn validate(&self, options: &Options, files: &[(PathBuf, FileFormat)], out: &mut dyn Write) -> Result<bool, std::io::Error> {
+        let root = self.root_type(options).map_err(std::io::Error::other)?;
+        let mut ok = true;
+        for (path, format) in files {
+            let mut problems = Vec::new();
+            match read(path).and_then(|input| Ok((format.peek_versions(&input)?, input))) {
+                Ok((versions, input)) => {
+                    if self.registry.type_keys().next().is_some() {
+                        for name in versions.keys() {
+                            if !self.registry.contains(name) {
+                                problems.push(format!("unknown type {}", name));
+                            }
+                        }
+                    }
+                    // Deserializing checks that all versions are supported
+                    if let Some(root) = root {
+                        if let Err(e) = format.upgrade(&self.registry, root, &input) {
+                            problems.push(e.to_string());
+                        }
+                    }
+                }
+                Err(e) => problems.push(e.to_string()),
+            }
+
+            if problems.is_empty() {
+                writeln!(out, "{}: ok", path.display())?;
+            } else {
+                ok = false;
+                for problem in problems {
+                    writeln!(out, "{}: {}", path.display(), problem)?;
+                }
+            }
+        }
+        Ok(ok)
+    }
+
+    fn upgrade(&self, options: &Options, files: &[(PathBuf, FileFormat)], out: &mut dyn Write) -> Result<bool, std::io::Error> {
+        let root = match self.root_type(options) {
+            Ok(Some(root)) => root,
+            Ok(None) if self.registry.type_keys().next().is_none() => {
+                return Err(std::io::Error::other("No types are registered. Register your types with serde_migrate_cli::Cli::register in your own binary to upgrade files"));
+            }
+            Ok(None) => {
+                return Err(std::io::Error::other(format!("Specify the type of the files with --type. Registered types: {}", self.type_names())));
+            }
+            Err(e) => return Err(std::io::Error::other(e)),
+        };
+
+        let mut ok = true;
+        for (path, format) in files {
+            let result = read(path).and_then(|input| format.upgrade(&self.registry, root, &input));
+            match result {
+                Ok((report, bytes)) => {
+                    let migrations: Vec<String> = report.migrations()
+                        .map(|(name, stored, latest)| format!("{} v{} -> v{}", name, stored, latest))
+                        .collect();
+                    if !report.changed {
+                        writeln!(out, "{}: up to date", path.display())?;
+                        continue;
+                    }
+                    if !options.dry_run {
+                        if let Err(e) = write_atomic(path, &bytes) {
+                            ok = false;
+                            writeln!(out, "{}: {}", path.display(), e)?;
+                            continue;
+                        }
+                    }
+                    let verb = if options.dry_run { "would upgrade" } else { "upgraded" };
+                    if migrations.is_empty() {
+                        writeln!(out, "{}: {}", path.display(), verb)?;
+                    } else {
+                        writeln!(out, "{}: {} {}", path.display(), verb, migrations.join(", "))?;
+                    }
+                }
+                Err(e) => {
+                    ok = false;
+                    writeln!(out, "{}: {}", path.display(), e)?;
+                }
+            }
+        }
+        Ok(ok)
+    }
+}
+
+fn parse_options(args: &[String]) -> Result<Options, String> {
+    let mut options = Options {
+        format: None,
+        type_name: None,
+        dry_run: false,
+        paths: Vec::new(),
+    };
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--format" => {
+                let name = args.next().ok_or("Missing value for --format")?;
+                options.format = Some(FileFormat::from_name(name).ok_or_else(|| format!("Unknown format '{}'", name))?);
+            }
+            "--type" => {
+                options.type_name = Some(args.next().ok_or("Missing value for --type")?.clone());
+            }
+            "--dry-run" => options.dry_run = true,
+            _ if arg.starts_with("--") => return Err(format!("Unknown option '{}'", arg)),
+            _ => options.paths.push(PathBuf::from(arg)),
+        }
+    }
+    if options.paths.is_empty() {
+        return Err("Missing paths".to_string());
+    }
+    Ok(options)
+}
+
+/// Collects the files to work on, searching directories recursively.
+///
+/// Files in directories whose format cannot be detected are skipped, but it is an error if a file given explicitly has an unknown format.
+fn collect_files(options: &Options) -> Result<Vec<(PathBuf, FileFormat)>, String> {
+    fn visit(dir: &Path, format: Option<FileFormat>, files: &mut Vec<(PathBuf, FileFormat)>) -> std::io::Result<()> {
+        let mut entries = fs::read_dir(dir)?.map(|e| e.map(|e| e.path())).collect::<Result<Vec<_>, _>>()?;
+        entries.sort();
+        for path in entries {
+            if path.is_dir() {
+                visit(&path, format, files)?;
+            } else if let Some(format) = format.or_else(|| FileFormat::from_path(&path)) {
+                files.push((path, format));
+            }
+        }
+        Ok(())
+    }
+
+    let mut files = Vec::new();
+    for path in &options.paths {
+        if path.is_dir() {
+            visit(path, options.format, &mut files).map_err(|e| format!("{}: {}", path.display(), e))?;
+        } else {
+            let format = options.format.or_else(|| FileFormat::from_path(path))
+                .ok_or_else(|| format!("{}: Unknown format. Use --format to specify it", path.display()))?;
+            files.push((path.clone(), format));
+        }
+    }
+    Ok(files)
+}
+
+fn read(path: &Path) -> Result<Vec<u8>, FormatError> {
+    Ok(fs::read(path)?)
+}
+
+/// Writes a file by writing to a temporary file next to it and renaming it, so that the file is never partially written.
+fn write_atomic(path: &Path, bytes: &[u8]) -> std::io::Result<()> {
+    let mut tmp_name = path.file_name().unwrap_or_default().to_owned();
+    tmp_name.push(".serde-migrate.tmp");
+    let tmp = path.with_file_name(tmp_name);
+    fs::write(&tmp, bytes)?;
+    fs::rename(&tmp, path).inspect_err(|_| {
+        let _ = fs::remove_file(&tmp);
+    })
+}
+
+fn header(files: &[(PathBuf, FileFormat)], out: &mut dyn Write) -> Result<bool, std::io::Error> {
+    let mut ok = true;
+    for (path, format) in files {
+        match read(path).and_then(|input| format.peek_versions(&input)) {
+            Ok(versions) => {
+                writeln!(out, "{} ({})", path.display(), format.name())?;
+                for (name, version) in versions {
+                    writeln!(out, "    {}: {}", name, version)?;
+                }
+            }
+            Err(e) => {
+                ok = false;
+                writeln!(out, "{}: {}", path.display(), e)?;
+            }
+        }
+    }
+    Ok(ok)
+}
+
+fn histogram(files: &[(PathBuf, FileFormat)], out: &mut dyn Write) -> Result<bool, std::io::Error> {
+    let mut counts: BTreeMap<(String, u32), usize> = BTreeMap::new();
+    let mut errors = Vec::new();
+    for (path, format) in files {
+        match read(path).and_then(|input| format.peek_versions(&input)) {
+            Ok(versions) => {
+                for (name, version) in versions {
+                    *counts.entry((name, version)).or_default() += 1;
+                }
+            }
+            Err(e) => errors.push((path, e)),
+        }
+    }
+
+    let width = counts.keys().map(|(name, _)| name.len()).max().unwrap_or(0).max("type".len());
+    writeln!(out, "{:<width$}  version  files", "type")?;
+    for ((name, version), count) in &counts {
+        writeln!(out, "{:<width$}  {:>7}  {:>5}", name, version, count)?;
+    }
+    for (path, e) in &errors {
+        writeln!(out, "{}: {}", path.display(), e)?;
+    }
+    Ok(errors.is_empty())
+}
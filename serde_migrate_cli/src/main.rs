@@ -0,0 +1,8 @@
+//! The `serde-migrate` command without any registered types.
+//!
+//! It can print and validate version headers, but it cannot upgrade files.
+//! To upgrade files, call [`serde_migrate_cli::Cli::run`] from your own `main`, with your types registered.
+
+fn main() {
+    serde_migrate_cli::Cli::new().run()
+}
@@ -0,0 +1,132 @@
+use std::path::Path;
+
+use tempfile::TempDir;
+
+use serde_migrate::{versioned, Versioned};
+use serde_migrate_cli::Cli;
+
+#[versioned]
+#[derive(PartialEq, Debug)]
+struct Event {
+    pub id: u32,
+    #[version(end = 2)]
+    pub name: String,
+    #[version(start = 2)]
+    pub label: String,
+}
+
+impl event_migrations::Migrate for Event {
+    fn to_v2(v: event_migrations::EventV1) -> event_migrations::EventV2 {
+        event_migrations::EventV2 { id: v.id, label: v.name }
+    }
+}
+
+#[versioned]
+#[derive(PartialEq, Debug)]
+struct Other {
+    pub x: u32,
+}
+
+fn temp_dir() -> TempDir {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::create_dir_all(dir.path().join("nested")).unwrap();
+    dir
+}
+
+fn write_fixtures(dir: &Path) {
+    std::fs::write(dir.join("a.json"), br#"{"versions":{"test_cli::Event":1},"value":{"id":1,"name":"a"}}"#).unwrap();
+    std::fs::write(dir.join("nested/b.json"), serde_json::to_vec(&Versioned(Event { id: 2, label: "b".to_string() })).unwrap()).unwrap();
+    std::fs::write(dir.join("nested/c.postcard"), postcard::to_stdvec(&Versioned(Event { id: 3, label: "c".to_string() })).unwrap()).unwrap();
+    std::fs::write(dir.join("notes.txt"), b"not versioned").unwrap();
+}
+
+fn run(cli: &Cli, args: &[&str]) -> (Result<bool, String>, String) {
+    let args: Vec<String> = args.iter().map(|a| a.to_string()).collect();
+    let mut out = Vec::new();
+    let result = cli.run_with_args(&args, &mut out);
+    (result, String::from_utf8(out).unwrap())
+}
+
+#[test]
+fn test_header_and_histogram() {
+    let tmp = temp_dir();
+    let dir = tmp.path();
+    write_fixtures(dir);
+    let path = dir.to_str().unwrap();
+
+    let (result, out) = run(&Cli::new(), &["header", &format!("{}/a.json", path)]);
+    assert_eq!(result, Ok(true));
+    assert!(out.ends_with("a.json (json)\n    test_cli::Event: 1\n"), "{}", out);
+
+    let (result, out) = run(&Cli::new(), &["histogram", path]);
+    assert_eq!(result, Ok(true));
+    let lines: Vec<&str> = out.lines().collect();
+    assert_eq!(lines, vec![
+        "type             version  files",
+        "test_cli::Event        1      1",
+        "test_cli::Event        2      2",
+    ]);
+}
+
+#[test]
+fn test_validate() {
+    let tmp = temp_dir();
+    let dir = tmp.path();
+    write_fixtures(dir);
+    std::fs::write(dir.join("future.json"), br#"{"versions":{"test_cli::Event":3},"value":{"id":1}}"#).unwrap();
+    std::fs::write(dir.join("other.json"), br#"{"versions":{"test_cli::Other":1},"value":{"x":1}}"#).unwrap();
+    let path = dir.to_str().unwrap();
+
+    // Without registered types, only the headers are checked
+    let (result, _) = run(&Cli::new(), &["validate", path]);
+    assert_eq!(result, Ok(true));
+
+    let cli = Cli::new().register::<Event>();
+    let (result, out) = run(&cli, &["validate", path]);
+    assert_eq!(result, Ok(false));
+    assert!(out.contains("a.json: ok"), "{}", out);
+    assert!(out.contains("c.postcard: ok"), "{}", out);
+    assert!(out.contains("future.json: Invalid version for Event (got 3)"), "{}", out);
+    assert!(out.contains("other.json: unknown type test_cli::Other"), "{}", out);
+}
+
+#[test]
+fn test_upgrade() {
+    let tmp = temp_dir();
+    let dir = tmp.path();
+    write_fixtures(dir);
+    let path = dir.to_str().unwrap();
+    let a = std::fs::read(dir.join("a.json")).unwrap();
+
+    let (result, _) = run(&Cli::new(), &["upgrade", path]);
+    assert!(result.unwrap_err().contains("No types are registered"));
+
+    let cli = Cli::new().register::<Event>().register::<Other>();
+    let (result, _) = run(&cli, &["upgrade", path]);
+    assert!(result.unwrap_err().contains("--type"));
+
+    let (result, out) = run(&cli, &["upgrade", "--type", "Event", "--dry-run", path]);
+    assert_eq!(result, Ok(true));
+    assert!(out.contains("a.json: would upgrade test_cli::Event v1 -> v2"), "{}", out);
+    assert!(out.contains("b.json: up to date"), "{}", out);
+    assert_eq!(std::fs::read(dir.join("a.json")).unwrap(), a);
+
+    let (result, out) = run(&cli, &["upgrade", "--type", "test_cli::Event", path]);
+    assert_eq!(result, Ok(true));
+    assert!(out.contains("a.json: upgraded test_cli::Event v1 -> v2"), "{}", out);
+    let upgraded: Versioned<Event> = serde_json::from_slice(&std::fs::read(dir.join("a.json")).unwrap()).unwrap();
+    assert_eq!(upgraded.0, Event { id: 1, label: "a".to_string() });
+
+    let (result, out) = run(&cli, &["upgrade", "--type", "Event", path]);
+    assert_eq!(result, Ok(true));
+    assert!(!out.contains("upgraded"), "{}", out);
+}
+
+#[test]
+fn test_invalid_arguments() {
+    assert!(run(&Cli::new(), &[]).0.is_err());
+    assert!(run(&Cli::new(), &["frobnicate", "."]).0.unwrap_err().contains("Unknown command"));
+    assert!(run(&Cli::new(), &["header"]).0.unwrap_err().contains("Missing paths"));
+    assert!(run(&Cli::new(), &["header", "--format", "xml", "."]).0.unwrap_err().contains("Unknown format"));
+    assert!(run(&Cli::new(), &["header", "file.txt"]).0.unwrap_err().contains("--format"));
+}
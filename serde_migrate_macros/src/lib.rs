@@ -1,18 +1,133 @@
 // #![feature(trace_macros)]
 // #![feature(log_syntax)]
 
-use std::default;
-
 use proc_macro::TokenStream;
 use quote::{quote, ToTokens, format_ident};
 
-use syn::{parse_macro_input, parse_quote, punctuated::Punctuated, token::Comma, DeriveInput, Expr, ExprLit, Ident, ImplGenerics, Token, Visibility, WherePredicate};
+use syn::{parse::Parser, parse_macro_input, parse_quote, punctuated::Punctuated, token::Comma, DeriveInput, Expr, ExprLit, Ident, Meta, Visibility, WherePredicate};
+
+/// Options passed to the root attribute, e.g. `#[versioned(preserve_unknown)]`.
+#[derive(Default)]
+struct RootOptions {
+    /// Capture fields that are not known by the latest version, and write them back when serializing.
+    preserve_unknown: bool,
+    /// The oldest version that can still be deserialized. All history before this version has been removed.
+    min_supported: Option<u32>,
+    /// Numeric id of the type, used by compact version headers.
+    id: Option<u16>,
+    /// Derive `schemars::JsonSchema` for every version. Requires the `schemars` feature of serde_migrate.
+    json_schema: bool,
+    /// Derive `arbitrary::Arbitrary` and `Serialize` for every version. Requires the `arbitrary` feature of serde_migrate.
+    arbitrary: bool,
+}
+
+fn parse_root_options(attr: TokenStream) -> syn::Result<RootOptions> {
+    let mut options = RootOptions::default();
+    let metas = Punctuated::<Meta, Comma>::parse_terminated.parse(attr)?;
+    for meta in metas {
+        match &meta {
+            Meta::Path(path) if path.is_ident("preserve_unknown") => {
+                options.preserve_unknown = true;
+            }
+            Meta::Path(path) if path.is_ident("json_schema") => {
+                options.json_schema = true;
+            }
+            Meta::Path(path) if path.is_ident("arbitrary") => {
+                options.arbitrary = true;
+            }
+            Meta::NameValue(nv) if nv.path.is_ident("min_supported") => {
+                let v: u32 = match &nv.value {
+                    Expr::Lit(ExprLit { lit: syn::Lit::Int(lit), .. }) => lit.base10_parse()?,
+                    _ => return Err(syn::Error::new_spanned(&nv.value, "Expected positive integer")),
+                };
+                if v == 0 {
+                    return Err(syn::Error::new_spanned(&nv.value, "Version numbers start at 1"));
+                }
+                options.min_supported = Some(v);
+            }
+            Meta::NameValue(nv) if nv.path.is_ident("id") => {
+                let id: u16 = match &nv.value {
+                    Expr::Lit(ExprLit { lit: syn::Lit::Int(lit), .. }) => lit.base10_parse()?,
+                    _ => return Err(syn::Error::new_spanned(&nv.value, "Expected an integer between 0 and 65535")),
+                };
+                options.id = Some(id);
+            }
+            _ => return Err(syn::Error::new_spanned(meta, "Unknown attribute. Expected 'preserve_unknown', 'json_schema', 'arbitrary', 'min_supported = x' or 'id = x'")),
+        }
+    }
+    Ok(options)
+}
+
+/// Formats a type like it is usually written, e.g. `Vec<String>` instead of the token stream's `Vec < String >`.
+///
+/// Types which are rarely used in fields, like trait objects and function pointers, are formatted as a token stream.
+fn type_string(ty: &syn::Type) -> String {
+    match ty {
+        syn::Type::Path(ty) if ty.qself.is_none() => path_string(&ty.path),
+        syn::Type::Reference(ty) => {
+            let lifetime = ty.lifetime.as_ref().map(|l| format!("{} ", l)).unwrap_or_default();
+            let mutability = if ty.mutability.is_some() { "mut " } else { "" };
+            format!("&{}{}{}", lifetime, mutability, type_string(&ty.elem))
+        }
+        syn::Type::Ptr(ty) => {
+            let mutability = if ty.mutability.is_some() { "mut" } else { "const" };
+            format!("*{} {}", mutability, type_string(&ty.elem))
+        }
+        syn::Type::Slice(ty) => format!("[{}]", type_string(&ty.elem)),
+        syn::Type::Array(ty) => format!("[{}; {}]", type_string(&ty.elem), ty.len.to_token_stream()),
+        syn::Type::Tuple(ty) => {
+            let elems = ty.elems.iter().map(type_string).collect::<Vec<_>>();
+            if elems.len() == 1 {
+                format!("({},)", elems[0])
+            } else {
+                format!("({})", elems.join(", "))
+            }
+        }
+        syn::Type::Paren(ty) => format!("({})", type_string(&ty.elem)),
+        syn::Type::Group(ty) => type_string(&ty.elem),
+        syn::Type::Never(_) => "!".to_owned(),
+        syn::Type::Infer(_) => "_".to_owned(),
+        ty => ty.to_token_stream().to_string(),
+    }
+}
+
+/// Formats a path like it is usually written, e.g. `std::collections::HashMap<String, u32>`.
+fn path_string(path: &syn::Path) -> String {
+    let segments = path.segments.iter().map(|segment| {
+        let arguments = match &segment.arguments {
+            syn::PathArguments::None => String::new(),
+            syn::PathArguments::AngleBracketed(arguments) => {
+                let arguments = arguments.args.iter().map(|argument| match argument {
+                    syn::GenericArgument::Type(ty) => type_string(ty),
+                    syn::GenericArgument::Lifetime(lifetime) => lifetime.to_string(),
+                    syn::GenericArgument::AssocType(assoc) => format!("{} = {}", assoc.ident, type_string(&assoc.ty)),
+                    argument => argument.to_token_stream().to_string(),
+                }).collect::<Vec<_>>();
+                format!("<{}>", arguments.join(", "))
+            }
+            syn::PathArguments::Parenthesized(arguments) => {
+                let inputs = arguments.inputs.iter().map(type_string).collect::<Vec<_>>();
+                match &arguments.output {
+                    syn::ReturnType::Default => format!("({})", inputs.join(", ")),
+                    syn::ReturnType::Type(_, output) => format!("({}) -> {}", inputs.join(", "), type_string(output)),
+                }
+            }
+        };
+        format!("{}{}", segment.ident, arguments)
+    }).collect::<Vec<_>>();
+    let leading = if path.leading_colon.is_some() { "::" } else { "" };
+    format!("{}{}", leading, segments.join("::"))
+}
 
 /// Macro for generating versioned serde serialization and deserialization implementations.
 ///
 /// See the crate-level documentation for more information.
 #[proc_macro_attribute]
-pub fn versioned(_root_attribute: TokenStream, item: TokenStream) -> TokenStream {
+pub fn versioned(root_attribute: TokenStream, item: TokenStream) -> TokenStream {
+    let options = match parse_root_options(root_attribute) {
+        Ok(options) => options,
+        Err(e) => return e.to_compile_error().into(),
+    };
     let original_ast = parse_macro_input!(item as DeriveInput);
 
     let mut versioned_ast = original_ast.clone();
@@ -81,10 +196,12 @@ pub fn versioned(_root_attribute: TokenStream, item: TokenStream) -> TokenStream
             match &mut struct_data.fields {
                 // for named field structs e.g. { A: int }
                 syn::Fields::Named(fields) => {
-                    let mut max_version = 1;
+                    // Versions before `min_supported` have been squashed, so the history starts there.
+                    let min_version: u32 = options.min_supported.unwrap_or(1);
+                    let mut max_version = min_version;
                     let mut versions = vec![];
                     for field in fields.named.iter() {
-                        let mut start = 1;
+                        let mut start = min_version;
                         let mut end = None;
                         for attr in &field.attrs {
                             if attr.path().is_ident("version") {
@@ -122,9 +239,13 @@ pub fn versioned(_root_attribute: TokenStream, item: TokenStream) -> TokenStream
                                             if s == "end" && v == 1 {
                                                 return syn::Error::new_spanned(assign.right.to_token_stream(), "Cannot remove fields in the first version".to_string()).to_compile_error().into()
                                             }
+                                            if s == "end" && v <= min_version {
+                                                return syn::Error::new_spanned(assign.right.to_token_stream(), format!("Field was removed before the minimum supported version ({}). Remove the field instead.", min_version)).to_compile_error().into()
+                                            }
 
                                             if s == "start" {
-                                                start = v;
+                                                // Fields added before the minimum supported version are present in all supported versions.
+                                                start = v.max(min_version);
                                             }
                                             if s == "end" {
                                                 end = Some(v);
@@ -142,8 +263,6 @@ pub fn versioned(_root_attribute: TokenStream, item: TokenStream) -> TokenStream
                         versions.push((start, end));
                     }
 
-                    let min_version: u32 = 1;
-
                     if max_version == min_version {
                         // return syn::Error::new_spanned(original_ast, "No versions were specified. Specify versions using the #[version(min=int, max=int)] attribute on fields.".to_string()).to_compile_error().into()
                     }
@@ -152,14 +271,60 @@ pub fn versioned(_root_attribute: TokenStream, item: TokenStream) -> TokenStream
                         field.attrs.retain(|a| !a.path().is_ident("version"));
                     }
 
+                    let schema_derive = if options.json_schema {
+                        quote!(
+                            #[derive(serde_migrate::schemars::JsonSchema)]
+                            #[schemars(crate = "serde_migrate::schemars")]
+                        )
+                    } else {
+                        quote!()
+                    };
+
+                    // The derive refers to `arbitrary::...`, which resolves to the re-export imported into the migrations module
+                    let arbitrary_derive = if options.arbitrary {
+                        quote!(#[derive(arbitrary::Arbitrary, serde::Serialize)])
+                    } else {
+                        quote!()
+                    };
+                    let arbitrary_import = if options.arbitrary {
+                        quote!(use serde_migrate::arbitrary;)
+                    } else {
+                        quote!()
+                    };
+
+                    let field_infos = fields.named.iter().zip(&versions).map(|(field, (start, end))| {
+                        let name = field.ident.as_ref().unwrap().to_string();
+                        let ty = type_string(&field.ty);
+                        let end = match end {
+                            Some(end) => quote!(Some(#end)),
+                            None => quote!(None),
+                        };
+                        quote!(serde_migrate::FieldInfo { name: #name, ty: #ty, start: #start, end: #end })
+                    }).collect::<Vec<_>>();
+
                     let mut versioned_structs = quote!();
                     let mut version_struct_names = vec![];
                     let mut versioned_variants = quote!();
+                    let mut fingerprints = quote!();
+                    let mut version_infos = quote!();
                     for v in min_version..=max_version {
+                        let version_fields = field_infos.iter().zip(&versions)
+                            .filter(|(_, (start, end))| *start <= v && end.unwrap_or(u32::MAX) > v)
+                            .map(|(info, _)| info)
+                            .collect::<Punctuated<_,Comma>>();
+                        version_infos.extend(quote!(serde_migrate::VersionInfo { version: #v, fields: &[#version_fields] },));
+
                         let mut versioned_fields = quote!();
+                        // The shape of the version, as it affects the serialized data: field names, order, types and serde attributes
+                        let mut fingerprint_fields = vec![];
                         for (i, field) in fields.named.iter().enumerate() {
                             let (start, end) = versions[i];
                             if start <= v && end.unwrap_or(u32::MAX) > v {
+                                let serde_attrs = field.attrs.iter()
+                                    .filter(|a| a.path().is_ident("serde"))
+                                    .map(|a| format!("{} ", a.to_token_stream()))
+                                    .collect::<String>();
+                                fingerprint_fields.push(format!("{}{}: {}", serde_attrs, field.ident.as_ref().unwrap(), type_string(&field.ty)));
                                 let mut field = field.clone();
                                 // Make the field public if it isn't already
                                 if !matches!(field.vis, Visibility::Public(_)) {
@@ -177,13 +342,21 @@ pub fn versioned(_root_attribute: TokenStream, item: TokenStream) -> TokenStream
 
                         versioned_structs.extend(quote!(
                             #[derive(serde::Deserialize)]
+                            #schema_derive
+                            #arbitrary_derive
                             #struct_extra_attrs
-                            pub(crate) struct #versioned_name #generics {
+                            pub struct #versioned_name #generics {
                                 #versioned_fields
                             }
                         ));
 
-                        versioned_variants.extend(quote!(#variant_name(#versioned_name #generics),))
+                        versioned_variants.extend(quote!(#variant_name(#versioned_name #generics),));
+
+                        let mut fingerprint = format!("{{ {} }}", fingerprint_fields.join(", "));
+                        if !struct_extra_attrs.is_empty() {
+                            fingerprint = format!("{} {}", struct_extra_attrs, fingerprint);
+                        }
+                        fingerprints.extend(quote!((#v, #fingerprint),));
                     }
 
                     // Remove all fields that are removed in the latest version
@@ -255,10 +428,13 @@ pub fn versioned(_root_attribute: TokenStream, item: TokenStream) -> TokenStream
                     // The only thing we will do with it is to convert it to the user-defined struct
                     let from_last_impl = {
                         let last_version = version_struct_names.last().unwrap();
-                        let field_copies = fields.named.iter().map(|f| {
+                        let mut field_copies = fields.named.iter().map(|f| {
                             let name = f.ident.as_ref().unwrap();
                             quote!(#name : v.#name)
                         }).collect::<Punctuated<_,Comma>>();
+                        if options.preserve_unknown {
+                            field_copies.push(quote!(unknown_fields: Default::default()));
+                        }
 
                         quote!{
                             impl #impl_generics From<#last_version #generics> for #struct_name #generics {
@@ -276,29 +452,214 @@ pub fn versioned(_root_attribute: TokenStream, item: TokenStream) -> TokenStream
                         quote!(#name : &self.#name)
                     }).collect::<Punctuated<_,Comma>>();
 
-                    let versioned_deserialization_cases = (min_version..=max_version).map(|v| {
+                    // With `preserve_unknown`, fields which are not known by the latest version are captured
+                    // in a hidden side map on the struct, and written back as-is when serializing.
+                    // The data is deserialized using a wrapper around the latest version, which flattens both
+                    // the known fields and the side map. Flattening only works in self-describing formats,
+                    // so formats which are not human readable use the latest version directly, unless there are unknown fields to write.
+                    let mut preserved_struct = quote!();
+                    let mut serialized_version = quote!(#max_version);
+                    let mut serialize_borrowed = quote!(borrowed.serialize(serializer));
+                    if options.preserve_unknown {
+                        let last_version = version_struct_names.last().unwrap();
+                        let (_, borrowed_generics, _) = generics_with_a_lifetime.split_for_impl();
+                        preserved_struct = quote! {
+                            #[derive(Deserialize)]
+                            pub(crate) struct Preserved #generics {
+                                #[serde(flatten)]
+                                pub(crate) value: #last_version #generics,
+                                #[serde(flatten)]
+                                pub(crate) unknown_fields: serde_migrate::UnknownFields,
+                            }
+
+                            #[derive(Serialize)]
+                            pub(crate) struct BorrowedPreserved #generics_with_a_lifetime {
+                                #[serde(flatten)]
+                                pub value: Borrowed #borrowed_generics,
+                                #[serde(flatten)]
+                                pub unknown_fields: &'a serde_migrate::UnknownFields,
+                            }
+                        };
+                        serialize_borrowed = quote! {
+                            if serializer.is_human_readable() || !self.unknown_fields.is_empty() || self.unknown_fields.stored_version().is_some_and(|v| v > #max_version) {
+                                #mod_name::serialization_helpers::BorrowedPreserved { value: borrowed, unknown_fields: &self.unknown_fields }.serialize(serializer)
+                            } else {
+                                borrowed.serialize(serializer)
+                            }
+                        };
+                        // Keep the version of newer data, so that it is not downgraded when written back.
+                        serialized_version = quote!(self.unknown_fields.stored_version().map_or(#max_version, |v| v.max(#max_version)));
+
+                        let unknown_field = syn::Field::parse_named.parse2(quote!(
+                            #[doc(hidden)]
+                            pub unknown_fields: serde_migrate::UnknownFields
+                        ));
+                        match unknown_field {
+                            Ok(f) => fields.named.push(f),
+                            Err(e) => return e.to_compile_error().into(),
+                        }
+                    }
+
+                    // The latest version is handled separately when preserving unknown fields,
+                    // since data from newer versions is also read using the latest version.
+                    let last_deserialized_version = if options.preserve_unknown { max_version - 1 } else { max_version };
+                    let deserialization_case = |v: u32| {
                         let variant_name = format_ident!("V{}", v.to_string());
                         let versioned_struct_name = &version_struct_names[(v-min_version) as usize];
                         quote!(#v => DataVersions #turbo_generics::#variant_name(#mod_name::#versioned_struct_name #turbo_generics::deserialize(deserializer)?))
-                    }).collect::<Punctuated<_,Comma>>();
+                    };
+                    let mut versioned_deserialization_cases = (min_version..=last_deserialized_version).map(deserialization_case).collect::<Punctuated<_,Comma>>();
+                    // Deserializing the stored representation never runs any migrations, so every version is read as-is.
+                    let mut stored_deserialization_cases = (min_version..=max_version).map(deserialization_case).collect::<Punctuated<_,Comma>>();
+                    let mut deserialization_result = quote!(Ok(result.migrate()));
+                    let mut unknown_fields_declaration = quote!();
+                    if options.preserve_unknown {
+                        let variant_name = format_ident!("V{}", max_version.to_string());
+                        let last_version = version_struct_names.last().unwrap();
+                        versioned_deserialization_cases.push(quote!(#max_version if !deserializer.is_human_readable() => {
+                            DataVersions #turbo_generics::#variant_name(#mod_name::#last_version #turbo_generics::deserialize(deserializer)?)
+                        }));
+                        versioned_deserialization_cases.push(quote!(v if v >= #max_version => {
+                            let preserved = #mod_name::serialization_helpers::Preserved #turbo_generics::deserialize(deserializer)?;
+                            unknown_fields = Some(preserved.unknown_fields.with_stored_version(v));
+                            DataVersions #turbo_generics::#variant_name(preserved.value)
+                        }));
+                        unknown_fields_declaration = quote!(let mut unknown_fields = None;);
+                        deserialization_result = quote!({
+                            let mut value = result.migrate();
+                            if let Some(unknown_fields) = unknown_fields {
+                                value.unknown_fields = unknown_fields;
+                            }
+                            Ok(value)
+                        });
+                    }
+                    if min_version > 1 {
+                        let too_old_message = format!("Version {{}} of {} is too old. The oldest supported version is {}", struct_name, min_version);
+                        let too_old_case = quote!(v if v < #min_version => return Err(serde::de::Error::custom(format!(#too_old_message, v))));
+                        versioned_deserialization_cases.push(too_old_case.clone());
+                        stored_deserialization_cases.push(too_old_case);
+                    }
                     let invalid_version_message = format!("Invalid version for {} (got {{}})", struct_name);
 
+                    let (get_version, set_version) = match options.id {
+                        Some(id) => (quote!(get_version_with_id::<Self, D>(#id)), quote!(set_version_with_id::<Self>(#serialized_version, #id))),
+                        None => (quote!(get_version::<Self, D>()), quote!(set_version::<Self>(#serialized_version))),
+                    };
+                    let version_lookup = quote! {
+                        serde_migrate::DESERIALIZATION_STATE.with(|state| {
+                            let mut state = state.borrow_mut();
+                            if let Some(state) = &mut *state {
+                                state.#get_version
+                            } else {
+                                Ok(#max_version)
+                            }
+                        })?
+                    };
+
+                    // With `json_schema`, the type has the schema of its latest version, and the schema of every version is available at runtime
+                    let mut json_schema_impl = quote!();
+                    if options.json_schema {
+                        let mut schema_generics = original_ast.generics.clone();
+                        for param in &mut schema_generics.params {
+                            if let syn::GenericParam::Type(ty) = param {
+                                ty.bounds.push(parse_quote!(serde_migrate::schemars::JsonSchema));
+                            }
+                        }
+                        let (schema_impl_generics, _, _) = schema_generics.split_for_impl();
+                        let last_version = version_struct_names.last().unwrap();
+                        let schema_cases = (min_version..=max_version).map(|v| {
+                            let versioned_struct_name = &version_struct_names[(v-min_version) as usize];
+                            quote!(#v => Some(serde_migrate::schemars::SchemaGenerator::default().into_root_schema_for::<#mod_name::#versioned_struct_name #generics>()))
+                        }).collect::<Punctuated<_,Comma>>();
+                        json_schema_impl = quote! {
+                            impl #schema_impl_generics serde_migrate::schemars::JsonSchema for #struct_name #generics {
+                                fn schema_name() -> std::borrow::Cow<'static, str> {
+                                    <#mod_name::#last_version #generics as serde_migrate::schemars::JsonSchema>::schema_name()
+                                }
+
+                                fn schema_id() -> std::borrow::Cow<'static, str> {
+                                    <#mod_name::#last_version #generics as serde_migrate::schemars::JsonSchema>::schema_id()
+                                }
+
+                                fn json_schema(generator: &mut serde_migrate::schemars::SchemaGenerator) -> serde_migrate::schemars::Schema {
+                                    <#mod_name::#last_version #generics as serde_migrate::schemars::JsonSchema>::json_schema(generator)
+                                }
+                            }
+
+                            impl #schema_impl_generics #struct_name #generics {
+                                /// The json schema of the given version, without the version header. Returns `None` if the version is not supported.
+                                pub fn json_schema_for_version(version: u32) -> Option<serde_migrate::schemars::Schema> {
+                                    match version {
+                                        #schema_cases,
+                                        _ => None,
+                                    }
+                                }
+                            }
+                        };
+                    }
+
+                    // With `arbitrary`, the type generates its latest version, and random data can be generated for every version
+                    let mut arbitrary_impl = quote!();
+                    if options.arbitrary {
+                        let mut arbitrary_generics = original_ast.generics.clone();
+                        arbitrary_generics.params.insert(0, parse_quote!('arbitrary));
+                        let mut versions_generics = original_ast.generics.clone();
+                        for param in &mut arbitrary_generics.params {
+                            if let syn::GenericParam::Type(ty) = param {
+                                ty.bounds.push(parse_quote!(serde_migrate::arbitrary::Arbitrary<'arbitrary>));
+                            }
+                        }
+                        for param in &mut versions_generics.params {
+                            if let syn::GenericParam::Type(ty) = param {
+                                ty.bounds.push(parse_quote!(for<'arbitrary> serde_migrate::arbitrary::Arbitrary<'arbitrary>));
+                                ty.bounds.push(parse_quote!(serde::Serialize));
+                                ty.bounds.push(parse_quote!(serde::de::DeserializeOwned));
+                            }
+                        }
+                        let (arbitrary_impl_generics, _, _) = arbitrary_generics.split_for_impl();
+                        let (versions_impl_generics, _, _) = versions_generics.split_for_impl();
+                        let last_version = version_struct_names.last().unwrap();
+                        let arbitrary_cases = (min_version..=max_version).map(|v| {
+                            let versioned_struct_name = &version_struct_names[(v-min_version) as usize];
+                            quote!(#v => serde_migrate::arbitrary_version_bytes::<Self, _, F>(#v, &<#mod_name::#versioned_struct_name #generics as serde_migrate::arbitrary::Arbitrary>::arbitrary(u)?))
+                        }).collect::<Punctuated<_,Comma>>();
+                        arbitrary_impl = quote! {
+                            impl #arbitrary_impl_generics serde_migrate::arbitrary::Arbitrary<'arbitrary> for #struct_name #generics {
+                                fn arbitrary(u: &mut serde_migrate::arbitrary::Unstructured<'arbitrary>) -> serde_migrate::arbitrary::Result<Self> {
+                                    <#mod_name::#last_version #generics as serde_migrate::arbitrary::Arbitrary>::arbitrary(u).map(Self::from)
+                                }
+                            }
+
+                            impl #versions_impl_generics serde_migrate::ArbitraryVersions for #struct_name #generics {
+                                fn arbitrary_version_bytes<F: serde_migrate::Format>(version: u32, u: &mut serde_migrate::arbitrary::Unstructured<'_>) -> serde_migrate::arbitrary::Result<Vec<u8>> {
+                                    match version {
+                                        #arbitrary_cases,
+                                        _ => Err(serde_migrate::arbitrary::Error::IncorrectFormat),
+                                    }
+                                }
+                            }
+                        };
+                    }
+
                     extra_ast = quote! {
                         pub(crate) mod #mod_name {
                             use super::*;
                             use super::#struct_name;
                             use serde::Deserialize;
+                            #arbitrary_import
 
                             #versioned_structs
 
                             #migration_trait
 
+                            pub(crate) use self::serialization_helpers::DataVersions;
+
                             pub(crate) mod serialization_helpers {
                                 use super::*;
                                 use super::{#struct_name, Migrate, #version_struct_idents};
                                 use serde::Serialize;
 
-                                pub(crate) enum DataVersions #generics {
+                                pub enum DataVersions #generics {
                                     #versioned_variants
                                 }
 
@@ -316,10 +677,15 @@ pub fn versioned(_root_attribute: TokenStream, item: TokenStream) -> TokenStream
                                     }
                                 }
 
+                                #preserved_struct
+
                                 #from_last_impl
                             }
                         }
 
+                        // Like serde's derives, the generated impls count as uses of the struct and its fields,
+                        // so that structs which are only ever deserialized are not reported as dead code.
+                        #[allow(dead_code)]
                         impl #impl_generics_with_serialize serde::ser::Serialize for #struct_name #generics {
                             fn serialize<S>(&self, mut serializer: S) -> Result<S::Ok, S::Error>
                             where
@@ -333,16 +699,18 @@ pub fn versioned(_root_attribute: TokenStream, item: TokenStream) -> TokenStream
                                 if std::any::type_name::<S>() == std::any::type_name::<&mut serde_migrate::VersionSerializer>() {
                                     unsafe {
                                         let state: &mut &mut serde_migrate::VersionSerializer = std::mem::transmute(&mut serializer);
-                                        state.set_version::<Self>(#max_version);
+                                        state.#set_version;
                                     }
                                 }
                                 
-                                return Borrowed #turbo_generics {
+                                let borrowed = Borrowed #turbo_generics {
                                     #field_copies_from_self
-                                }.serialize(serializer)
+                                };
+                                #serialize_borrowed
                             }
                         }
 
+                        #[allow(dead_code)]
                         impl #generics_with_de_lifetime serde::de::Deserialize<'de> for #struct_name #generics {
                             fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
                             where
@@ -350,20 +718,60 @@ pub fn versioned(_root_attribute: TokenStream, item: TokenStream) -> TokenStream
                             {
                                 use #mod_name::serialization_helpers::DataVersions;
 
-                                let v = serde_migrate::DESERIALIZATION_STATE.with(|state| {
-                                    let mut state = state.borrow_mut();
-                                    if let Some(state) = &mut *state {
-                                        state.get_version::<Self, D>()
-                                    } else {
-                                        Ok(#max_version)
-                                    }
-                                })?;
+                                let v = #version_lookup;
 
+                                #unknown_fields_declaration
                                 let result = match v {
                                     #versioned_deserialization_cases,
                                     _ => return Err(serde::de::Error::custom(format!(#invalid_version_message, v))),
                                 };
-                                Ok(result.migrate())
+                                #deserialization_result
+                            }
+                        }
+
+                        impl #impl_generics serde_migrate::MigrateStored for #struct_name #generics {
+                            type Stored = #mod_name::serialization_helpers::DataVersions #generics;
+
+                            fn migrate_stored(stored: Self::Stored) -> Self {
+                                stored.migrate()
+                            }
+                        }
+
+                        #json_schema_impl
+
+                        #arbitrary_impl
+
+                        impl #impl_generics serde_migrate::VersionedType for #struct_name #generics {
+                            const CURRENT_VERSION: u32 = #max_version;
+                            const MIN_SUPPORTED_VERSION: u32 = #min_version;
+
+                            fn type_key() -> &'static str {
+                                std::any::type_name::<Self>()
+                            }
+
+                            fn history() -> &'static [serde_migrate::VersionInfo] {
+                                &[#version_infos]
+                            }
+                        }
+
+                        impl #impl_generics serde_migrate::SchemaFingerprints for #struct_name #generics {
+                            const FINGERPRINTS: &'static [(u32, &'static str)] = &[#fingerprints];
+                        }
+
+                        impl #generics_with_de_lifetime serde_migrate::DeserializeStored<'de> for #struct_name #generics {
+                            fn deserialize_stored<D>(deserializer: D) -> Result<(u32, Self::Stored), D::Error>
+                            where
+                                D: serde::de::Deserializer<'de>,
+                            {
+                                use #mod_name::serialization_helpers::DataVersions;
+                                use serde::de::Deserialize;
+
+                                let v = #version_lookup;
+                                let result = match v {
+                                    #stored_deserialization_cases,
+                                    _ => return Err(serde::de::Error::custom(format!(#invalid_version_message, v))),
+                                };
+                                Ok((v, result))
                             }
                         }
                     };